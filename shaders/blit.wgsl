@@ -1,9 +1,17 @@
 #include blit_common.wgsl
 
+#ifdef MULTIVIEW
 @fragment
 fn blit_fs_main(
     in: BlitVertexOutput,
     @builtin(view_index) view_index: i32
 ) -> @location(0) vec4<f32> {
     return textureSample(blit_texture, blit_sampler, in.uv_coords, i32(view_index));
-}
\ No newline at end of file
+}
+#else
+var<push_constant> view_index: u32;
+@fragment
+fn blit_fs_main(in: BlitVertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(blit_texture, blit_sampler, in.uv_coords, i32(view_index));
+}
+#endif
\ No newline at end of file
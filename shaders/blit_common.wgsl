@@ -1,3 +1,5 @@
+#pragma once
+
 struct BlitVertexInput {
     @location(0) position: vec3<f32>,
     @location(1) uv_coords: vec2<f32>,
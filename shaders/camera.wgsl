@@ -0,0 +1,16 @@
+#pragma once
+
+// Everything known about a single view of the camera. Matches `CameraUniform` in `src/camera.rs`.
+struct CameraView {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_projection: mat4x4<f32>,
+    inverse_view: mat4x4<f32>,
+    inverse_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    // The position of the eye in world space.
+    eye_position: vec3<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: array<CameraView, VIEW_COUNT>;
@@ -1,5 +1,4 @@
-@group(0) @binding(0)
-var<uniform> view_projection_matrix: array<mat4x4<f32>, 2>;
+#include camera.wgsl
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -31,7 +30,7 @@ fn vs_main(
     );
 
     var out: VertexOutput;
-    out.position = view_projection_matrix[view_index] * model_matrix * vec4<f32>(model.position, 1.0);
+    out.position = camera[view_index].view_projection * model_matrix * vec4<f32>(model.position, 1.0);
     out.color = model.color;
     return out;
 }
@@ -0,0 +1,58 @@
+//! Runs the WGSL preprocessor over a shader directory and writes the flattened output of each
+//! entry point, so that it can be inspected, diffed, or passed to external tools without
+//! launching the renderer.
+//!
+//!   cargo run --bin wgsl_preprocess -- shaders out -D VIEW_COUNT=2 -D MULTIVIEW --line-map
+
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Parser;
+use wgpu_openxr_example::wgsl;
+
+#[derive(Parser)]
+#[command(author, version, about)]
+struct Args {
+    /// The directory containing the shaders
+    input: PathBuf,
+    /// The directory to write the preprocessed entry points to
+    output: PathBuf,
+    /// A define to preprocess with, as `NAME` or `NAME=VALUE`; can be repeated
+    #[arg(short = 'D', long = "define")]
+    defines: Vec<String>,
+    /// Also write a `.map` file next to each output, mapping its lines back to the original files
+    #[arg(long)]
+    line_map: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let mut defines = wgsl::Defines::new();
+    for define in &args.defines {
+        let (name, value) = define.split_once('=').unwrap_or((define, ""));
+        defines.insert(name, value);
+    }
+
+    let preprocessor = wgsl::Preprocessor::from_directory(&args.input)
+        .with_context(|| format!("failed to read `{}`", args.input.display()))?;
+    for entry_point in preprocessor.entry_points() {
+        let preprocessed = preprocessor.preprocess(entry_point, &defines)?;
+
+        let output_path = args.output.join(entry_point);
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&output_path, &preprocessed.source)
+            .with_context(|| format!("failed to write `{}`", output_path.display()))?;
+        if args.line_map {
+            let mut map_path = output_path.clone().into_os_string();
+            map_path.push(".map");
+            std::fs::write(&map_path, preprocessed.line_map.to_string())?;
+        }
+
+        println!("{} -> {}", entry_point.display(), output_path.display());
+    }
+
+    Ok(())
+}
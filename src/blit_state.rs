@@ -1,39 +1,50 @@
 use glam::{vec3, Vec3};
-use std::borrow::Cow;
+use std::{num::NonZeroU32, rc::Rc};
 use wgpu::util::DeviceExt;
 
+use crate::{
+    shader_cache::{PipelineState, ShaderCache},
+    wgsl::{Defines, Preprocessor},
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlitVertex {
+    position: [f32; 3],
+    uv_coords: [f32; 2],
+}
+impl BlitVertex {
+    fn new(position: Vec3, uv_coords: [f32; 2]) -> Self {
+        Self {
+            position: position.to_array(),
+            uv_coords,
+        }
+    }
+}
+
 pub struct BlitState {
     sampler: wgpu::Sampler,
     bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
-    render_pipeline_window: wgpu::RenderPipeline,
-    render_pipeline_headset: wgpu::RenderPipeline,
+    pipeline_layout: Rc<wgpu::PipelineLayout>,
+    window_swapchain_format: wgpu::TextureFormat,
+    headset_swapchain_format: wgpu::TextureFormat,
+    headset_view_count: u32,
+    render_pipeline_window: Rc<wgpu::RenderPipeline>,
+    render_pipeline_headset: Rc<wgpu::RenderPipeline>,
     #[allow(dead_code)]
     vertex_buffer: wgpu::Buffer,
 }
 impl BlitState {
     pub fn new(
         device: &wgpu::Device,
-        preprocessor: &crate::wgsl::Preprocessor,
+        preprocessor: &Preprocessor,
+        shader_cache: &mut ShaderCache,
         render_target_view: &wgpu::TextureView,
         window_swapchain_format: wgpu::TextureFormat,
         headset_swapchain_format: wgpu::TextureFormat,
+        headset_view_count: u32,
     ) -> Self {
-        #[repr(C)]
-        #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-        struct BlitVertex {
-            position: [f32; 3],
-            uv_coords: [f32; 2],
-        }
-        impl BlitVertex {
-            fn new(position: Vec3, uv_coords: [f32; 2]) -> Self {
-                Self {
-                    position: position.to_array(),
-                    uv_coords,
-                }
-            }
-        }
-
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
@@ -68,61 +79,26 @@ impl BlitState {
         let bind_group =
             Self::create_bind_group(device, &bind_group_layout, render_target_view, &sampler);
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[wgpu::PushConstantRange {
-                stages: wgpu::ShaderStages::FRAGMENT,
-                range: 0..4,
-            }],
-        });
-        let vertex_buffer_layout = wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<BlitVertex>() as _,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 3]>() as _,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-            ],
-        };
-
-        let [render_pipeline_window, render_pipeline_headset] = [
-            ("blit_window.wgsl", window_swapchain_format, None),
-            ("blit_headset.wgsl", headset_swapchain_format, Some(2)),
-        ]
-        .map(|(filename, swapchain_format, multiview)| {
-            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: None,
-                source: wgpu::ShaderSource::Wgsl(Cow::Owned(
-                    preprocessor.preprocess(filename).unwrap(),
-                )),
-            });
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        let pipeline_layout = Rc::new(device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
                 label: None,
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "blit_vs_main",
-                    buffers: &[vertex_buffer_layout.clone()],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "blit_fs_main",
-                    targets: &[Some(swapchain_format.into())],
-                }),
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                multiview: multiview.map(|i| i.try_into().unwrap()),
-            })
-        });
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[wgpu::PushConstantRange {
+                    stages: wgpu::ShaderStages::FRAGMENT,
+                    range: 0..4,
+                }],
+            },
+        ));
+        let [render_pipeline_window, render_pipeline_headset] = Self::create_pipelines(
+            device,
+            preprocessor,
+            shader_cache,
+            &pipeline_layout,
+            window_swapchain_format,
+            headset_swapchain_format,
+            headset_view_count,
+        )
+        .unwrap();
 
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Blit Vertex Buffer"),
@@ -142,12 +118,113 @@ impl BlitState {
             sampler,
             bind_group_layout,
             bind_group,
+            pipeline_layout,
+            window_swapchain_format,
+            headset_swapchain_format,
+            headset_view_count,
             render_pipeline_window,
             render_pipeline_headset,
             vertex_buffer,
         }
     }
 
+    /// Rebuilds the pipelines from the current files in `preprocessor`. If the new shaders fail
+    /// to compile, the existing pipelines are kept and the error is returned.
+    pub fn reload_shaders(
+        &mut self,
+        device: &wgpu::Device,
+        preprocessor: &Preprocessor,
+        shader_cache: &mut ShaderCache,
+    ) -> anyhow::Result<()> {
+        [self.render_pipeline_window, self.render_pipeline_headset] = Self::create_pipelines(
+            device,
+            preprocessor,
+            shader_cache,
+            &self.pipeline_layout,
+            self.window_swapchain_format,
+            self.headset_swapchain_format,
+            self.headset_view_count,
+        )?;
+        Ok(())
+    }
+
+    fn create_pipelines(
+        device: &wgpu::Device,
+        preprocessor: &Preprocessor,
+        shader_cache: &mut ShaderCache,
+        pipeline_layout: &Rc<wgpu::PipelineLayout>,
+        window_swapchain_format: wgpu::TextureFormat,
+        headset_swapchain_format: wgpu::TextureFormat,
+        headset_view_count: u32,
+    ) -> anyhow::Result<[Rc<wgpu::RenderPipeline>; 2]> {
+        let vertex_buffer_layout = wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<BlitVertex>() as _,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as _,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+            ],
+        };
+
+        let [window, headset] = [
+            ("blit_window", Defines::new(), window_swapchain_format, None),
+            (
+                "blit_headset",
+                Defines::new().with_flag("MULTIVIEW"),
+                headset_swapchain_format,
+                NonZeroU32::new(headset_view_count),
+            ),
+        ]
+        .map(|(label, defines, format, multiview)| {
+            let state = PipelineState {
+                layout: pipeline_layout,
+                format,
+                depth_mode: None,
+                multiview,
+                topology: wgpu::PrimitiveTopology::TriangleList,
+            };
+            shader_cache.pipeline(
+                device,
+                preprocessor,
+                "blit.wgsl",
+                &defines,
+                state,
+                |shader| {
+                    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some(label),
+                        layout: Some(state.layout),
+                        vertex: wgpu::VertexState {
+                            module: shader,
+                            entry_point: "blit_vs_main",
+                            buffers: &[vertex_buffer_layout.clone()],
+                        },
+                        fragment: Some(wgpu::FragmentState {
+                            module: shader,
+                            entry_point: "blit_fs_main",
+                            targets: &[Some(state.format.into())],
+                        }),
+                        primitive: wgpu::PrimitiveState {
+                            topology: state.topology,
+                            ..Default::default()
+                        },
+                        depth_stencil: None,
+                        multisample: wgpu::MultisampleState::default(),
+                        multiview: state.multiview,
+                    })
+                },
+            )
+        });
+        Ok([window?, headset?])
+    }
+
     pub fn resize(&mut self, device: &wgpu::Device, render_target_view: &wgpu::TextureView) {
         self.bind_group = Self::create_bind_group(
             device,
@@ -1,75 +1,192 @@
-use glam::{vec3, vec4, Mat4, Vec3};
-use wgpu::util::DeviceExt;
+use anyhow::Context;
+use glam::{vec3, Mat4, Vec3};
 
-pub struct PerspectiveCamera {
+use crate::{
+    frustum::Frustum,
+    types::DepthMode,
+    xr_math::{self, Fov},
+};
+
+/// The default interpupillary distance, in metres.
+pub const DEFAULT_IPD: f32 = 63.0 / 1_000.0;
+/// The nearest the eyes' views can converge, in metres.
+pub const MIN_CONVERGENCE_DISTANCE: f32 = 0.1;
+
+/// The camera data for a single view. Matches `CameraView` in `camera.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view: [[f32; 4]; 4],
+    pub projection: [[f32; 4]; 4],
+    pub view_projection: [[f32; 4]; 4],
+    pub inverse_view: [[f32; 4]; 4],
+    pub inverse_projection: [[f32; 4]; 4],
+    pub inverse_view_projection: [[f32; 4]; 4],
+    /// The position of the eye in world space.
+    pub eye_position: [f32; 3],
+    _padding: f32,
+}
+impl CameraUniform {
+    pub fn new(view: Mat4, projection: Mat4) -> Self {
+        let view_projection = projection * view;
+        let inverse_view = view.inverse();
+        Self {
+            view: view.to_cols_array_2d(),
+            projection: projection.to_cols_array_2d(),
+            view_projection: view_projection.to_cols_array_2d(),
+            inverse_view: inverse_view.to_cols_array_2d(),
+            inverse_projection: projection.inverse().to_cols_array_2d(),
+            inverse_view_projection: view_projection.inverse().to_cols_array_2d(),
+            eye_position: inverse_view.w_axis.truncate().to_array(),
+            _padding: 0.0,
+        }
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_projection(Mat4::from_cols_array_2d(&self.view_projection))
+    }
+}
+
+/// How a [Camera] projects view space onto the screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    Perspective {
+        fov_y_rad: f32,
+    },
+    /// A parallel projection, for overlays and maps.
+    Orthographic {
+        /// The height of the visible area in metres; its width follows from the aspect ratio.
+        height: f32,
+    },
+    /// A projection matrix used as-is. It must map depth as the camera's
+    /// [DepthMode] expects for depth testing to work.
+    Custom(Mat4),
+}
+impl Projection {
+    /// Parses a [Projection::Custom] from the 16 comma-separated numbers of its matrix, in
+    /// column-major order.
+    pub fn parse_custom(source: &str) -> anyhow::Result<Self> {
+        let numbers = source
+            .split(',')
+            .map(|token| {
+                let token = token.trim();
+                token
+                    .parse::<f32>()
+                    .with_context(|| format!("invalid number {token:?}"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        anyhow::ensure!(
+            numbers.len() == 16,
+            "expected 16 numbers, got {}",
+            numbers.len()
+        );
+        Ok(Self::Custom(Mat4::from_cols_slice(&numbers)))
+    }
+}
+
+pub struct Camera {
     pub eye: Vec3,
     pub target: Vec3,
     pub up: Vec3,
 
     pub aspect_ratio: f32,
-    pub fov_y_rad: f32,
+    pub projection: Projection,
 
     pub z_near: f32,
+    /// Ignored for perspective projections if `depth_mode` is [DepthMode::ReverseZInfinite].
     pub z_far: f32,
+    pub depth_mode: DepthMode,
+
+    /// The distance between the eyes in metres, used when rendering in stereo.
+    pub ipd: f32,
+    /// The distance in metres at which the eyes' views converge, at least
+    /// [MIN_CONVERGENCE_DISTANCE]. `f32::INFINITY` gives parallel views. Only used by perspective
+    /// projections.
+    pub convergence_distance: f32,
+    /// Whether to render a single view from `eye` instead of a view per eye.
+    pub mono: bool,
 }
-impl PerspectiveCamera {
-    pub fn to_view_proj_matrices(&self) -> Vec<f32> {
-        let ipd = 63.0 / 1_000.0;
-        let offset = vec4(ipd / 2.0, 0.0, 0.0, 0.0);
+impl Camera {
+    /// The number of views produced by [Self::to_view_uniforms].
+    pub fn view_count(&self) -> u32 {
+        if self.mono {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// The projection matrix for the desktop views.
+    pub fn projection_matrix(&self) -> Mat4 {
+        match self.projection {
+            Projection::Perspective { fov_y_rad } => xr_math::fov_projection(
+                Fov::symmetric(fov_y_rad, self.aspect_ratio),
+                self.depth_mode,
+                self.z_near,
+                self.z_far,
+            ),
+            Projection::Orthographic { height } => {
+                let (half_width, half_height) = (height * self.aspect_ratio / 2.0, height / 2.0);
+                // An orthographic projection can't have an infinite far plane, so both reverse-Z
+                // modes use `z_far`.
+                let (z_near, z_far) = match self.depth_mode {
+                    DepthMode::Standard => (self.z_near, self.z_far),
+                    DepthMode::ReverseZ | DepthMode::ReverseZInfinite => (self.z_far, self.z_near),
+                };
+                Mat4::orthographic_rh(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    z_near,
+                    z_far,
+                )
+            }
+            Projection::Custom(matrix) => matrix,
+        }
+    }
 
+    /// Returns one view if [Self::mono] is set and a view per eye otherwise.
+    pub fn to_view_uniforms(&self) -> Vec<CameraUniform> {
         let view = Mat4::look_at_rh(self.eye, self.target, self.up);
-        let proj = Mat4::perspective_rh(self.fov_y_rad, self.aspect_ratio, self.z_near, self.z_far);
+        let proj = self.projection_matrix();
+        if self.mono {
+            return vec![CameraUniform::new(view, proj)];
+        }
 
-        [-offset, offset]
-            .map(|o| {
-                let mut view = view;
-                view.w_axis += view * o;
-                (proj * view).to_cols_array()
+        [-self.ipd / 2.0, self.ipd / 2.0]
+            .map(|eye_offset| {
+                // Move the eye sideways in view space, then shift its frustum back towards the
+                // centre so that both views line up at the convergence distance.
+                let view = Mat4::from_translation(vec3(-eye_offset, 0.0, 0.0)) * view;
+                let mut proj = proj;
+                if let Projection::Perspective { .. } = self.projection {
+                    proj.z_axis.x -= proj.x_axis.x * eye_offset / self.convergence_distance;
+                }
+                CameraUniform::new(view, proj)
             })
-            .concat()
+            .to_vec()
     }
 
+    /// Uses the poses of the XR views. Perspective projections use the views' fields of view,
+    /// while other projections are used as they are for every view.
     #[cfg(feature = "xr")]
-    pub fn to_view_proj_matrices_with_xr_views(&self, views: &[openxr::View]) -> Vec<f32> {
+    pub fn to_view_uniforms_with_xr_views(&self, views: &[openxr::View]) -> Vec<CameraUniform> {
         views
             .iter()
-            .flat_map(|v| {
-                let pose = v.pose;
-                let (xr_translation, xr_rotation) = crate::xr::openxr_pose_to_glam(&pose);
-
-                let view = Mat4::look_at_rh(
-                    self.eye + xr_translation,
-                    self.eye + xr_translation + xr_rotation * Vec3::Z,
-                    xr_rotation * Vec3::Y,
-                );
-
-                let [tan_left, tan_right, tan_down, tan_up] = [
-                    v.fov.angle_left,
-                    v.fov.angle_right,
-                    v.fov.angle_down,
-                    v.fov.angle_up,
-                ]
-                .map(f32::tan);
-                let tan_width = tan_right - tan_left;
-                let tan_height = tan_up - tan_down;
-
-                let a11 = 2.0 / tan_width;
-                let a22 = 2.0 / tan_height;
-
-                let a31 = (tan_right + tan_left) / tan_width;
-                let a32 = (tan_up + tan_down) / tan_height;
-                let a33 = -self.z_far / (self.z_far - self.z_near);
-
-                let a43 = -(self.z_far * self.z_near) / (self.z_far - self.z_near);
-
-                let proj = glam::Mat4::from_cols_array(&[
-                    a11, 0.0, 0.0, 0.0, //
-                    0.0, a22, 0.0, 0.0, //
-                    a31, a32, a33, -1.0, //
-                    0.0, 0.0, a43, 0.0, //
-                ]);
-
-                (proj * view).to_cols_array()
+            .map(|v| {
+                let (xr_translation, xr_rotation) = crate::xr::openxr_pose_to_glam(&v.pose);
+                let view = xr_math::view_matrix(self.eye + xr_translation, xr_rotation);
+                let proj = match self.projection {
+                    Projection::Perspective { .. } => xr_math::fov_projection(
+                        v.fov.into(),
+                        self.depth_mode,
+                        self.z_near,
+                        self.z_far,
+                    ),
+                    _ => self.projection_matrix(),
+                };
+                CameraUniform::new(view, proj)
             })
             .collect()
     }
@@ -80,35 +197,43 @@ impl PerspectiveCamera {
 }
 
 pub struct CameraState {
-    pub data: PerspectiveCamera,
+    pub data: Camera,
+    view_count: u32,
 
     buffer: wgpu::Buffer,
     bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
 }
 impl CameraState {
-    pub fn new(device: &wgpu::Device, inner_size: winit::dpi::PhysicalSize<u32>) -> Self {
-        let data = PerspectiveCamera {
+    /// Creates a camera for rendering `view_count` views.
+    pub fn new(
+        device: &wgpu::Device,
+        inner_size: winit::dpi::PhysicalSize<u32>,
+        view_count: u32,
+    ) -> Self {
+        let data = Camera {
             eye: Vec3::ZERO,
             target: vec3(0.0, 0.0, 1.0),
             up: Vec3::Y,
 
             aspect_ratio: inner_size.width as f32 / inner_size.height as f32,
-            fov_y_rad: 90.0f32.to_radians(),
+            projection: Projection::Perspective {
+                fov_y_rad: 90.0f32.to_radians(),
+            },
 
             z_near: 0.05,
             z_far: 1000.0,
+            depth_mode: DepthMode::ReverseZ,
+
+            ipd: DEFAULT_IPD,
+            convergence_distance: f32::INFINITY,
+            mono: false,
         };
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera buffer"),
-            contents: bytemuck::cast_slice(&data.to_view_proj_matrices()),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Camera Bind Group Layout"),
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -117,23 +242,54 @@ impl CameraState {
                 count: None,
             }],
         });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Camera Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-        });
+        let (buffer, bind_group) =
+            Self::create_buffer_and_bind_group(device, &bind_group_layout, view_count);
 
         Self {
             data,
+            view_count,
             buffer,
             bind_group_layout,
             bind_group,
         }
     }
 
+    /// The number of views rendered, which may be more than [Camera::to_view_uniforms] produces.
+    pub fn view_count(&self) -> u32 {
+        self.view_count
+    }
+
+    /// Resizes the buffer to hold `view_count` views. The bind group is replaced, but its layout
+    /// is kept.
+    pub fn set_view_count(&mut self, device: &wgpu::Device, view_count: u32) {
+        self.view_count = view_count;
+        (self.buffer, self.bind_group) =
+            Self::create_buffer_and_bind_group(device, &self.bind_group_layout, view_count);
+    }
+
+    fn create_buffer_and_bind_group(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        view_count: u32,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        // Sized for every view, as the camera may produce fewer views than are rendered.
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Camera buffer"),
+            size: (std::mem::size_of::<CameraUniform>() * view_count as usize) as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Camera Bind Group"),
+            layout: bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        (buffer, bind_group)
+    }
+
     pub fn buffer(&self) -> &wgpu::Buffer {
         &self.buffer
     }
@@ -144,3 +300,148 @@ impl CameraState {
         &self.bind_group_layout
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::{Defines, Preprocessor};
+
+    const EPSILON: f32 = 1e-5;
+    const Z_NEAR: f32 = 0.05;
+    const Z_FAR: f32 = 1000.0;
+    const DEPTH_MODES: [DepthMode; 3] = [
+        DepthMode::Standard,
+        DepthMode::ReverseZ,
+        DepthMode::ReverseZInfinite,
+    ];
+
+    /// A camera at the origin looking down -Z, so that view space is world space.
+    fn camera(projection: Projection, depth_mode: DepthMode) -> Camera {
+        Camera {
+            eye: Vec3::ZERO,
+            target: -Vec3::Z,
+            up: Vec3::Y,
+            aspect_ratio: 16.0 / 9.0,
+            projection,
+            z_near: Z_NEAR,
+            z_far: Z_FAR,
+            depth_mode,
+            ipd: DEFAULT_IPD,
+            convergence_distance: 2.0,
+            mono: false,
+        }
+    }
+
+    fn project(uniform: &CameraUniform, point: Vec3) -> Vec3 {
+        Mat4::from_cols_array_2d(&uniform.view_projection).project_point3(point)
+    }
+
+    #[test]
+    fn orthographic_maps_depth_range() {
+        for depth_mode in DEPTH_MODES {
+            let camera = camera(Projection::Orthographic { height: 4.0 }, depth_mode);
+            let projection = camera.projection_matrix();
+            let near = projection.project_point3(vec3(0.0, 2.0, -Z_NEAR));
+            let far = projection.project_point3(vec3(0.0, -2.0, -Z_FAR));
+
+            let (near_depth, far_depth) = match depth_mode {
+                DepthMode::Standard => (0.0, 1.0),
+                DepthMode::ReverseZ | DepthMode::ReverseZInfinite => (1.0, 0.0),
+            };
+            assert!(
+                (near.z - near_depth).abs() < EPSILON,
+                "{depth_mode:?}: {near}"
+            );
+            assert!((far.z - far_depth).abs() < EPSILON, "{depth_mode:?}: {far}");
+            assert!((near.y - 1.0).abs() < EPSILON, "{depth_mode:?}: {near}");
+            assert!((far.y + 1.0).abs() < EPSILON, "{depth_mode:?}: {far}");
+        }
+    }
+
+    #[test]
+    fn projection_matrix_follows_projection() {
+        let fov_y_rad = 60.0f32.to_radians();
+        for depth_mode in DEPTH_MODES {
+            let perspective = camera(Projection::Perspective { fov_y_rad }, depth_mode);
+            assert_eq!(
+                perspective.projection_matrix(),
+                xr_math::fov_projection(
+                    Fov::symmetric(fov_y_rad, perspective.aspect_ratio),
+                    depth_mode,
+                    Z_NEAR,
+                    Z_FAR
+                )
+            );
+
+            let matrix = Mat4::from_scale(vec3(1.0, 2.0, 3.0));
+            let projection =
+                Projection::parse_custom("1,0,0,0, 0,2,0,0, 0,0,3,0, 0,0,0,1").unwrap();
+            assert_eq!(projection, Projection::Custom(matrix));
+            let custom = camera(projection, depth_mode);
+            assert_eq!(custom.projection_matrix(), matrix);
+        }
+    }
+
+    #[test]
+    fn parse_custom_reports_bad_matrices() {
+        let error = Projection::parse_custom("1,0,0").unwrap_err();
+        assert_eq!(error.to_string(), "expected 16 numbers, got 3");
+        let error = Projection::parse_custom("1,x").unwrap_err();
+        assert_eq!(error.to_string(), "invalid number \"x\"");
+    }
+
+    #[test]
+    fn stereo_views_are_ipd_apart_and_converge() {
+        let camera = camera(
+            Projection::Perspective {
+                fov_y_rad: 90.0f32.to_radians(),
+            },
+            DepthMode::ReverseZ,
+        );
+        assert_eq!(camera.view_count(), 2);
+        let [left, right]: [CameraUniform; 2] = camera.to_view_uniforms().try_into().unwrap();
+        let eye_offset = Vec3::from(right.eye_position) - Vec3::from(left.eye_position);
+        assert!((eye_offset - vec3(camera.ipd, 0.0, 0.0)).length() < EPSILON);
+
+        // A point on the centre line lands in the same place in both views at the convergence
+        // distance, and on opposite sides of the centre elsewhere.
+        let converged = vec3(0.0, 0.0, -camera.convergence_distance);
+        let (left_point, right_point) = (project(&left, converged), project(&right, converged));
+        assert!((left_point - right_point).length() < EPSILON);
+        let far = vec3(0.0, 0.0, -10.0 * camera.convergence_distance);
+        assert!(project(&left, far).x < -EPSILON && project(&right, far).x > EPSILON);
+
+        let camera = Camera {
+            mono: true,
+            ..camera
+        };
+        let views = camera.to_view_uniforms();
+        assert_eq!(views.len(), 1);
+        assert_eq!(camera.view_count(), 1);
+        assert_eq!(Vec3::from(views[0].eye_position), camera.eye);
+    }
+
+    #[test]
+    fn uniform_matches_shader_layout() {
+        let module = Preprocessor::embedded(None)
+            .unwrap()
+            .preprocess("camera.wgsl", &Defines::new().with("VIEW_COUNT", 2))
+            .unwrap()
+            .validate(wgpu::Features::empty())
+            .unwrap();
+        let span = module
+            .types
+            .iter()
+            .find_map(|(_, ty)| match &ty.inner {
+                naga::TypeInner::Struct { span, .. }
+                    if ty.name.as_deref() == Some("CameraView") =>
+                {
+                    Some(*span)
+                }
+                _ => None,
+            })
+            .expect("camera.wgsl should define CameraView");
+
+        assert_eq!(std::mem::size_of::<CameraUniform>(), span as usize);
+    }
+}
@@ -0,0 +1,306 @@
+//! Recording and playback of the camera and controllers, one frame at a time.
+//!
+//! Paths are stored as text with a header line followed by a line per frame, each being
+//! whitespace-separated numbers: the time, the desktop camera's eye and target, the number of XR
+//! views followed by each view's position, orientation and field of view, and then the state of
+//! each hand's actions in the order of [ACTIONS]. Booleans are written as 0 or 1, and poses as a
+//! tracking flag followed by the translation and rotation if they are tracked. Outputs have no
+//! state, so nothing is written for them.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::Context;
+use glam::{Quat, Vec2, Vec3};
+
+use crate::{
+    input::{ActionKind, ActionValue, HandState, ACTIONS},
+    xr_math::Fov,
+};
+
+const HEADER: &str = "wgpu-openxr-example camera path 2";
+
+/// An XR view as reported by OpenXR, i.e. in its stage space and conventions.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ViewPose {
+    pub position: Vec3,
+    pub orientation: Quat,
+    pub fov: Fov,
+}
+
+/// Everything needed to reproduce the camera and controllers for a frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraFrame {
+    /// The time since the program started, in seconds.
+    pub time: f32,
+    pub eye: Vec3,
+    pub target: Vec3,
+    /// The XR views, if the frame was rendered with them.
+    pub views: Vec<ViewPose>,
+    /// The state of each hand's actions, indexed by [crate::input::Hand].
+    pub hands: [HandState; 2],
+}
+impl fmt::Display for CameraFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `{:?}` prints the shortest representation that parses back to the same value.
+        let mut numbers = vec![self.time];
+        numbers.extend(self.eye.to_array());
+        numbers.extend(self.target.to_array());
+        numbers.push(self.views.len() as f32);
+        for view in &self.views {
+            numbers.extend(view.position.to_array());
+            numbers.extend(view.orientation.to_array());
+            numbers.extend([
+                view.fov.angle_left,
+                view.fov.angle_right,
+                view.fov.angle_up,
+                view.fov.angle_down,
+            ]);
+        }
+        for hand in &self.hands {
+            for action in &ACTIONS {
+                match hand.get(action.id) {
+                    ActionValue::Boolean(pressed) => numbers.push(pressed as u8 as f32),
+                    ActionValue::Float(value) => numbers.push(value),
+                    ActionValue::Vector2(value) => numbers.extend(value.to_array()),
+                    ActionValue::Pose(pose) => {
+                        numbers.push(pose.is_some() as u8 as f32);
+                        if let Some((translation, rotation)) = pose {
+                            numbers.extend(translation.to_array());
+                            numbers.extend(rotation.to_array());
+                        }
+                    }
+                    ActionValue::Haptic => {}
+                }
+            }
+        }
+
+        for (i, number) in numbers.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{number:?}")?;
+        }
+        Ok(())
+    }
+}
+impl FromStr for CameraFrame {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> anyhow::Result<Self> {
+        let mut numbers = line.split_whitespace().map(|token| {
+            token
+                .parse::<f32>()
+                .with_context(|| format!("invalid number {token:?}"))
+        });
+        let mut next = |count: usize| -> anyhow::Result<Vec<f32>> {
+            (0..count)
+                .map(|_| numbers.next().context("line ended early")?)
+                .collect()
+        };
+        let vec3 = |n: &[f32]| Vec3::from_slice(n);
+        let quat = |n: &[f32]| Quat::from_slice(n);
+
+        let time = next(1)?[0];
+        let camera = next(6)?;
+        let view_count = next(1)?[0] as usize;
+        let views = (0..view_count)
+            .map(|_| {
+                let n = next(11)?;
+                Ok(ViewPose {
+                    position: vec3(&n[0..3]),
+                    orientation: quat(&n[3..7]),
+                    fov: Fov {
+                        angle_left: n[7],
+                        angle_right: n[8],
+                        angle_up: n[9],
+                        angle_down: n[10],
+                    },
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let mut hand = || -> anyhow::Result<HandState> {
+            let mut state = HandState::default();
+            for action in &ACTIONS {
+                let value = match action.kind {
+                    ActionKind::Boolean => ActionValue::Boolean(next(1)?[0] != 0.0),
+                    ActionKind::Float => ActionValue::Float(next(1)?[0]),
+                    ActionKind::Vector2 => ActionValue::Vector2(Vec2::from_slice(&next(2)?)),
+                    ActionKind::Pose => ActionValue::Pose(if next(1)?[0] != 0.0 {
+                        let n = next(7)?;
+                        Some((vec3(&n[0..3]), quat(&n[3..7])))
+                    } else {
+                        None
+                    }),
+                    ActionKind::Haptic => ActionValue::Haptic,
+                };
+                state.set(action.id, value);
+            }
+            Ok(state)
+        };
+        let hands = [hand()?, hand()?];
+        anyhow::ensure!(numbers.next().is_none(), "unexpected data at end of line");
+
+        Ok(Self {
+            time,
+            eye: vec3(&camera[0..3]),
+            target: vec3(&camera[3..6]),
+            views,
+            hands,
+        })
+    }
+}
+
+/// Writes a frame at a time to a camera path file.
+pub struct CameraPathRecorder {
+    writer: BufWriter<File>,
+}
+impl CameraPathRecorder {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let mut writer = BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("failed to create camera path {}", path.display()))?,
+        );
+        writeln!(writer, "{HEADER}")?;
+        Ok(Self { writer })
+    }
+
+    pub fn record(&mut self, frame: &CameraFrame) -> std::io::Result<()> {
+        writeln!(self.writer, "{frame}")
+    }
+
+    /// Writes out any buffered frames. This must be called before exiting, as the event loop
+    /// never drops its resources.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Plays back the frames of a camera path file in order.
+pub struct CameraPathPlayer {
+    frames: Vec<CameraFrame>,
+    next: usize,
+    started: Option<std::time::Instant>,
+}
+impl CameraPathPlayer {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read camera path {}", path.display()))?;
+        Self::parse(&source).with_context(|| format!("invalid camera path {}", path.display()))
+    }
+
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut lines = source.lines().enumerate();
+        anyhow::ensure!(
+            lines.next().map(|(_, line)| line) == Some(HEADER),
+            "missing header {HEADER:?}"
+        );
+        let frames = lines
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| line.parse().with_context(|| format!("line {}", i + 1)))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            frames,
+            next: 0,
+            started: None,
+        })
+    }
+
+    /// Returns the next frame, or `None` once every frame has been played.
+    pub fn next_frame(&mut self) -> Option<&CameraFrame> {
+        self.started.get_or_insert_with(std::time::Instant::now);
+        let frame = self.frames.get(self.next)?;
+        self.next += 1;
+        Some(frame)
+    }
+
+    /// The number of frames played back so far.
+    pub fn frames_played(&self) -> usize {
+        self.next
+    }
+
+    /// The time since the first frame was played.
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.started.map(|s| s.elapsed()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::ActionId;
+    use glam::vec3;
+
+    #[test]
+    fn frames_round_trip() {
+        let desktop = CameraFrame {
+            time: 1.0 / 3.0,
+            eye: vec3(0.1, 1.7, -2.0),
+            target: vec3(0.1, 1.7, -1.0),
+            views: vec![],
+            hands: Default::default(),
+        };
+        let mut left_hand = HandState::default();
+        let grip = (vec3(0.2, 1.2, 0.4), Quat::from_rotation_x(1.0));
+        let aim = (vec3(0.2, 1.25, 0.35), Quat::from_rotation_x(0.5));
+        left_hand.set(ActionId::GripPose, ActionValue::Pose(Some(grip)));
+        left_hand.set(ActionId::AimPose, ActionValue::Pose(Some(aim)));
+        left_hand.set(ActionId::Trigger, ActionValue::Float(0.6));
+        left_hand.set(
+            ActionId::Thumbstick,
+            ActionValue::Vector2(Vec2::new(-0.25, 1.0)),
+        );
+        left_hand.set(ActionId::PrimaryButton, ActionValue::Boolean(true));
+        let xr = CameraFrame {
+            views: [-0.032, 0.032]
+                .map(|x| ViewPose {
+                    position: vec3(x, 1.6, 0.0),
+                    orientation: Quat::from_rotation_y(0.3),
+                    fov: Fov {
+                        angle_left: -0.9,
+                        angle_right: 0.7,
+                        angle_up: 0.8,
+                        angle_down: -0.95,
+                    },
+                })
+                .to_vec(),
+            hands: [left_hand, HandState::default()],
+            ..desktop.clone()
+        };
+
+        let source = format!("{HEADER}\n{desktop}\n{xr}\n");
+        let mut player = CameraPathPlayer::parse(&source).unwrap();
+        assert_eq!(player.next_frame(), Some(&desktop));
+        assert_eq!(player.next_frame(), Some(&xr));
+        assert_eq!(player.next_frame(), None);
+        assert_eq!(player.frames_played(), 2);
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let frame = CameraFrame {
+            time: 0.0,
+            eye: Vec3::ZERO,
+            target: Vec3::ZERO,
+            views: vec![],
+            hands: Default::default(),
+        };
+        let error = CameraPathPlayer::parse(&format!("{HEADER}\n{frame}\n0 0 0"))
+            .err()
+            .unwrap();
+        assert_eq!(format!("{error:#}"), "line 3: line ended early");
+        let error = CameraPathPlayer::parse(&format!("{HEADER}\n{frame} 0"))
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{error:#}"),
+            "line 2: unexpected data at end of line"
+        );
+        assert!(CameraPathPlayer::parse(&frame.to_string()).is_err());
+    }
+}
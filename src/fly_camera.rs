@@ -0,0 +1,144 @@
+use std::collections::HashSet;
+
+use glam::{vec3, Quat, Vec3};
+use winit::{
+    event::{ElementState, ModifiersState, VirtualKeyCode},
+    window::{CursorGrabMode, Window},
+};
+
+use crate::camera::Camera;
+
+/// A first-person camera controller for the desktop: WASD to move, Space/E and C/Q to rise and
+/// fall, and the mouse to look around while the cursor is grabbed.
+pub struct FlyCamera {
+    /// Rotation around the Y axis in radians; 0 looks down +Z.
+    pub yaw: f32,
+    /// Rotation above the horizon in radians.
+    pub pitch: f32,
+    /// Movement speed in metres per second.
+    pub speed: f32,
+    /// Radians turned per pixel of mouse movement.
+    pub sensitivity: f32,
+
+    pressed: HashSet<VirtualKeyCode>,
+    modifiers: ModifiersState,
+    grabbed: bool,
+}
+impl FlyCamera {
+    const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+    /// Speed multiplier while Shift is held.
+    const FAST_MULTIPLIER: f32 = 4.0;
+    /// Speed multiplier while Ctrl is held.
+    const SLOW_MULTIPLIER: f32 = 0.25;
+
+    /// Creates a controller looking in the same direction as `camera`.
+    pub fn new(camera: &Camera) -> Self {
+        let direction = (camera.target - camera.eye).normalize_or_zero();
+        Self {
+            yaw: direction.x.atan2(direction.z),
+            pitch: direction.y.asin().clamp(-Self::MAX_PITCH, Self::MAX_PITCH),
+            speed: 2.0,
+            sensitivity: 0.002,
+
+            pressed: HashSet::new(),
+            modifiers: ModifiersState::empty(),
+            grabbed: false,
+        }
+    }
+
+    /// Whether the cursor is currently grabbed for mouse look.
+    pub fn grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    /// Grabs or releases the cursor. Mouse movement only turns the camera while it is grabbed.
+    pub fn set_grabbed(&mut self, window: &Window, grabbed: bool) {
+        if grabbed {
+            // Not every platform supports locking the cursor in place, so fall back to keeping it
+            // within the window.
+            if let Err(e) = window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+            {
+                log::warn!("failed to grab cursor: {e}");
+                return;
+            }
+        } else if let Err(e) = window.set_cursor_grab(CursorGrabMode::None) {
+            log::warn!("failed to release cursor: {e}");
+        }
+        window.set_cursor_visible(!grabbed);
+        self.grabbed = grabbed;
+    }
+
+    /// Records a key being pressed or released. Keys the camera doesn't use are ignored.
+    pub fn handle_key(&mut self, key: VirtualKeyCode, state: ElementState) {
+        match state {
+            ElementState::Pressed => self.pressed.insert(key),
+            ElementState::Released => self.pressed.remove(&key),
+        };
+    }
+
+    pub fn handle_modifiers(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    /// Turns the camera by a raw mouse movement, if the cursor is grabbed.
+    pub fn handle_mouse_motion(&mut self, (dx, dy): (f64, f64)) {
+        if !self.grabbed {
+            return;
+        }
+        self.yaw -= dx as f32 * self.sensitivity;
+        self.pitch =
+            (self.pitch - dy as f32 * self.sensitivity).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+
+    /// Forgets all held keys, e.g. when the window loses focus and would miss their release.
+    pub fn release_all(&mut self) {
+        self.pressed.clear();
+        self.modifiers = ModifiersState::empty();
+    }
+
+    /// The unit vector the camera is looking along.
+    pub fn forward(&self) -> Vec3 {
+        vec3(
+            self.yaw.sin() * self.pitch.cos(),
+            self.pitch.sin(),
+            self.yaw.cos() * self.pitch.cos(),
+        )
+    }
+
+    /// The rotation that turns +Z into [Self::forward] while keeping +X horizontal.
+    pub fn rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.yaw) * Quat::from_rotation_x(-self.pitch)
+    }
+
+    /// The velocity requested by the held keys, in metres per second, with `up` as the
+    /// direction to rise in.
+    pub fn velocity(&self, up: Vec3) -> Vec3 {
+        use VirtualKeyCode as K;
+        let forward = self.forward();
+        let right = forward.cross(up).normalize_or_zero();
+        let axis = |positive: &[K], negative: &[K]| {
+            let held = |keys: &[K]| keys.iter().any(|k| self.pressed.contains(k));
+            held(positive) as i32 as f32 - held(negative) as i32 as f32
+        };
+        let movement = forward * axis(&[K::W], &[K::S])
+            + right * axis(&[K::D], &[K::A])
+            + up * axis(&[K::Space, K::E], &[K::C, K::Q]);
+
+        let mut speed = self.speed;
+        if self.modifiers.shift() {
+            speed *= Self::FAST_MULTIPLIER;
+        }
+        if self.modifiers.ctrl() {
+            speed *= Self::SLOW_MULTIPLIER;
+        }
+        movement.normalize_or_zero() * speed
+    }
+
+    /// Moves `camera` by the held keys over `dt` seconds and points it along [Self::forward].
+    pub fn update(&self, camera: &mut Camera, dt: f32) {
+        camera.eye += self.velocity(camera.up) * dt;
+        camera.target = camera.eye + self.forward();
+    }
+}
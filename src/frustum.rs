@@ -0,0 +1,191 @@
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+
+/// A half-line from `origin` along the unit vector `direction`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+/// A sphere that encloses some geometry, used for visibility tests and ray casts.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+impl BoundingSphere {
+    /// A sphere enclosing all of `points`, centred on their bounding box.
+    pub fn enclosing(points: &[Vec3]) -> Self {
+        let (min, max) = points.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), &point| (min.min(point), max.max(point)),
+        );
+        let center = (min + max) / 2.0;
+        let radius = points
+            .iter()
+            .map(|point| point.distance(center))
+            .fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    /// The distance along `ray` at which it enters the sphere, which is 0 if it starts inside,
+    /// or `None` if it misses.
+    pub fn intersect_ray(&self, ray: Ray) -> Option<f32> {
+        let to_center = self.center - ray.origin;
+        let along = to_center.dot(ray.direction);
+        let miss_distance_squared = to_center.length_squared() - along * along;
+        let radius_squared = self.radius * self.radius;
+        if miss_distance_squared > radius_squared {
+            return None;
+        }
+        let half_chord = (radius_squared - miss_distance_squared).sqrt();
+        // The sphere is entirely behind the ray if it is exited before the origin.
+        (along + half_chord >= 0.0).then(|| (along - half_chord).max(0.0))
+    }
+}
+
+/// The volume visible through a view-projection matrix, as six planes facing inwards.
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+impl Frustum {
+    /// Extracts the planes of the clip volume of `view_projection`, which is wgpu's
+    /// `-w <= x <= w`, `-w <= y <= w` and `0 <= z <= w`. This works for every
+    /// [crate::types::DepthMode], as they only swap which of the depth planes is near.
+    pub fn from_view_projection(view_projection: Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_projection.row(i));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            // An infinite far plane has no normal; its constant term is positive, so it contains
+            // everything without normalisation.
+            let length = plane.xyz().length();
+            if length > f32::EPSILON {
+                plane / length
+            } else {
+                plane
+            }
+        });
+        Self { planes }
+    }
+
+    /// Whether any part of `sphere` may be inside the frustum. Spheres near the frustum's
+    /// corners can be reported as inside when they aren't.
+    pub fn intersects_sphere(&self, sphere: BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(sphere.center) + plane.w >= -sphere.radius)
+    }
+}
+
+/// Whether `sphere` may be visible from any of `frusta`, i.e. intersects their union.
+pub fn any_intersects_sphere(frusta: &[Frustum], sphere: BoundingSphere) -> bool {
+    frusta
+        .iter()
+        .any(|frustum| frustum.intersects_sphere(sphere))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        types::DepthMode,
+        xr_math::{fov_projection, Fov},
+    };
+    use glam::vec3;
+
+    fn sphere(center: Vec3, radius: f32) -> BoundingSphere {
+        BoundingSphere { center, radius }
+    }
+
+    fn frustum(depth_mode: DepthMode, view: Mat4) -> Frustum {
+        let projection = fov_projection(
+            Fov::symmetric(90f32.to_radians(), 1.0),
+            depth_mode,
+            0.1,
+            100.0,
+        );
+        Frustum::from_view_projection(projection * view)
+    }
+
+    #[test]
+    fn culls_spheres_outside_each_plane() {
+        for depth_mode in [
+            DepthMode::Standard,
+            DepthMode::ReverseZ,
+            DepthMode::ReverseZInfinite,
+        ] {
+            let frustum = frustum(depth_mode, Mat4::IDENTITY);
+            assert!(frustum.intersects_sphere(sphere(vec3(0.0, 0.0, -10.0), 0.5)));
+            // Straddling the left edge, and just beyond it.
+            assert!(frustum.intersects_sphere(sphere(vec3(-10.5, 0.0, -10.0), 1.0)));
+            assert!(!frustum.intersects_sphere(sphere(vec3(-12.0, 0.0, -10.0), 1.0)));
+            assert!(!frustum.intersects_sphere(sphere(vec3(12.0, 0.0, -10.0), 1.0)));
+            assert!(!frustum.intersects_sphere(sphere(vec3(0.0, -12.0, -10.0), 1.0)));
+            assert!(!frustum.intersects_sphere(sphere(vec3(0.0, 12.0, -10.0), 1.0)));
+            // Behind the eye.
+            assert!(!frustum.intersects_sphere(sphere(vec3(0.0, 0.0, 2.0), 1.0)));
+
+            let beyond_far_plane = sphere(vec3(0.0, 0.0, -200.0), 1.0);
+            assert_eq!(
+                frustum.intersects_sphere(beyond_far_plane),
+                depth_mode == DepthMode::ReverseZInfinite
+            );
+        }
+    }
+
+    #[test]
+    fn union_of_eye_frusta() {
+        let eyes = [-1.0, 1.0].map(|x| {
+            frustum(
+                DepthMode::ReverseZ,
+                Mat4::from_translation(vec3(-x, 0.0, 0.0)),
+            )
+        });
+        // Only visible to the right eye.
+        let right_only = sphere(vec3(11.5, 0.0, -10.0), 0.5);
+        assert!(!eyes[0].intersects_sphere(right_only));
+        assert!(eyes[1].intersects_sphere(right_only));
+        assert!(any_intersects_sphere(&eyes, right_only));
+        assert!(!any_intersects_sphere(
+            &eyes,
+            sphere(vec3(0.0, 0.0, 5.0), 0.5)
+        ));
+    }
+
+    #[test]
+    fn enclosing_sphere() {
+        let sphere = BoundingSphere::enclosing(&[
+            vec3(-1.0, -1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(1.0, -1.0, 0.0),
+        ]);
+        assert_eq!(sphere.center, Vec3::ZERO);
+        assert!((sphere.radius - 2f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ray_intersections() {
+        let sphere = sphere(vec3(0.0, 0.0, 5.0), 1.0);
+        let ray = |origin: Vec3, direction: Vec3| Ray {
+            origin,
+            direction: direction.normalize(),
+        };
+        assert_eq!(sphere.intersect_ray(ray(Vec3::ZERO, Vec3::Z)), Some(4.0));
+        // Grazing the side, and just missing it.
+        assert_eq!(
+            sphere.intersect_ray(ray(vec3(1.0, 0.0, 0.0), Vec3::Z)),
+            Some(5.0)
+        );
+        assert_eq!(
+            sphere.intersect_ray(ray(vec3(1.1, 0.0, 0.0), Vec3::Z)),
+            None
+        );
+        // Starting inside, and pointing away from it.
+        assert_eq!(
+            sphere.intersect_ray(ray(vec3(0.0, 0.0, 5.5), Vec3::Z)),
+            Some(0.0)
+        );
+        assert_eq!(sphere.intersect_ray(ray(Vec3::ZERO, -Vec3::Z)), None);
+        assert_eq!(sphere.intersect_ray(ray(Vec3::ZERO, Vec3::X)), None);
+    }
+}
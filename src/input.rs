@@ -0,0 +1,501 @@
+//! The controller's input and output actions, described as data so that the OpenXR actions and
+//! their suggested bindings can be generated from them, the state of those actions for each hand,
+//! and the joints of hands that are tracked directly.
+//!
+//! Every action is created once with a subaction path per hand, so that the same action reports
+//! each hand's state separately.
+
+use std::time::Duration;
+
+use glam::{Quat, Vec2, Vec3};
+
+/// A hand, holding a controller.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Hand {
+    Left,
+    Right,
+}
+impl Hand {
+    pub const ALL: [Hand; 2] = [Hand::Left, Hand::Right];
+
+    /// The OpenXR top-level user path for the hand.
+    pub fn path(self) -> &'static str {
+        match self {
+            Hand::Left => "/user/hand/left",
+            Hand::Right => "/user/hand/right",
+        }
+    }
+}
+
+/// The type of an action's state.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ActionKind {
+    Boolean,
+    Float,
+    Vector2,
+    Pose,
+    /// An output that vibrates the controller.
+    Haptic,
+}
+
+/// Identifies an action in [ACTIONS], which is in the same order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ActionId {
+    GripPose,
+    AimPose,
+    Trigger,
+    Squeeze,
+    Thumbstick,
+    ThumbstickClick,
+    /// A on a right controller, or X on a left one.
+    PrimaryButton,
+    /// B on a right controller, or Y on a left one.
+    SecondaryButton,
+    Menu,
+    Haptic,
+}
+impl ActionId {
+    pub fn desc(self) -> &'static ActionDesc {
+        &ACTIONS[self as usize]
+    }
+}
+
+/// An action as it is created in OpenXR.
+#[derive(Debug)]
+pub struct ActionDesc {
+    pub id: ActionId,
+    /// The action's name, which must be lowercase and unique within the action set.
+    pub name: &'static str,
+    pub localized_name: &'static str,
+    pub kind: ActionKind,
+}
+
+/// Every action, indexed by [ActionId].
+pub const ACTIONS: [ActionDesc; 10] = [
+    ActionDesc {
+        id: ActionId::GripPose,
+        name: "grip_pose",
+        localized_name: "Grip Pose",
+        kind: ActionKind::Pose,
+    },
+    ActionDesc {
+        id: ActionId::AimPose,
+        name: "aim_pose",
+        localized_name: "Aim Pose",
+        kind: ActionKind::Pose,
+    },
+    ActionDesc {
+        id: ActionId::Trigger,
+        name: "trigger",
+        localized_name: "Trigger",
+        kind: ActionKind::Float,
+    },
+    ActionDesc {
+        id: ActionId::Squeeze,
+        name: "squeeze",
+        localized_name: "Squeeze",
+        kind: ActionKind::Float,
+    },
+    ActionDesc {
+        id: ActionId::Thumbstick,
+        name: "thumbstick",
+        localized_name: "Thumbstick",
+        kind: ActionKind::Vector2,
+    },
+    ActionDesc {
+        id: ActionId::ThumbstickClick,
+        name: "thumbstick_click",
+        localized_name: "Thumbstick Click",
+        kind: ActionKind::Boolean,
+    },
+    ActionDesc {
+        id: ActionId::PrimaryButton,
+        name: "primary_button",
+        localized_name: "Primary Button",
+        kind: ActionKind::Boolean,
+    },
+    ActionDesc {
+        id: ActionId::SecondaryButton,
+        name: "secondary_button",
+        localized_name: "Secondary Button",
+        kind: ActionKind::Boolean,
+    },
+    ActionDesc {
+        id: ActionId::Menu,
+        name: "menu",
+        localized_name: "Menu",
+        kind: ActionKind::Boolean,
+    },
+    ActionDesc {
+        id: ActionId::Haptic,
+        name: "haptic",
+        localized_name: "Haptic Feedback",
+        kind: ActionKind::Haptic,
+    },
+];
+
+/// Suggests a component of each hand's controller for an action.
+#[derive(Debug)]
+pub struct Binding {
+    pub action: ActionId,
+    /// The component's path relative to the left hand's path, if the left controller has it.
+    pub left: Option<&'static str>,
+    /// The component's path relative to the right hand's path, if the right controller has it.
+    pub right: Option<&'static str>,
+}
+impl Binding {
+    /// Binds `action` to the same component on both controllers.
+    pub const fn both(action: ActionId, component: &'static str) -> Self {
+        Self {
+            action,
+            left: Some(component),
+            right: Some(component),
+        }
+    }
+
+    /// Binds `action` to a different component on each controller.
+    pub const fn each(action: ActionId, left: &'static str, right: &'static str) -> Self {
+        Self {
+            action,
+            left: Some(left),
+            right: Some(right),
+        }
+    }
+
+    /// Binds `action` to a component that only the left controller has.
+    pub const fn left(action: ActionId, component: &'static str) -> Self {
+        Self {
+            action,
+            left: Some(component),
+            right: None,
+        }
+    }
+
+    /// The full paths of the bound components.
+    pub fn paths(&self) -> impl Iterator<Item = String> + '_ {
+        [(Hand::Left, self.left), (Hand::Right, self.right)]
+            .into_iter()
+            .filter_map(|(hand, component)| Some(format!("{}/{}", hand.path(), component?)))
+    }
+}
+
+/// The bindings suggested for an interaction profile. Actions without a binding are never active
+/// with that profile.
+#[derive(Debug)]
+pub struct InteractionProfile {
+    pub path: &'static str,
+    pub bindings: &'static [Binding],
+}
+
+/// The profile every runtime supports. It only has a select and a menu button, so the select
+/// button pulls the trigger.
+pub const SIMPLE_CONTROLLER: InteractionProfile = InteractionProfile {
+    path: "/interaction_profiles/khr/simple_controller",
+    bindings: &[
+        Binding::both(ActionId::GripPose, "input/grip/pose"),
+        Binding::both(ActionId::AimPose, "input/aim/pose"),
+        Binding::both(ActionId::Haptic, "output/haptic"),
+        Binding::both(ActionId::Trigger, "input/select/click"),
+        Binding::both(ActionId::Menu, "input/menu/click"),
+    ],
+};
+
+pub const OCULUS_TOUCH: InteractionProfile = InteractionProfile {
+    path: "/interaction_profiles/oculus/touch_controller",
+    bindings: &[
+        Binding::both(ActionId::GripPose, "input/grip/pose"),
+        Binding::both(ActionId::AimPose, "input/aim/pose"),
+        Binding::both(ActionId::Haptic, "output/haptic"),
+        Binding::both(ActionId::Trigger, "input/trigger/value"),
+        Binding::both(ActionId::Squeeze, "input/squeeze/value"),
+        Binding::both(ActionId::Thumbstick, "input/thumbstick"),
+        Binding::both(ActionId::ThumbstickClick, "input/thumbstick/click"),
+        Binding::each(ActionId::PrimaryButton, "input/x/click", "input/a/click"),
+        Binding::each(ActionId::SecondaryButton, "input/y/click", "input/b/click"),
+        // The right controller's equivalent is reserved for the runtime.
+        Binding::left(ActionId::Menu, "input/menu/click"),
+    ],
+};
+
+/// The Index controllers have no menu button, so only the system button could stand in for it,
+/// and that is reserved for the runtime.
+pub const VALVE_INDEX: InteractionProfile = InteractionProfile {
+    path: "/interaction_profiles/valve/index_controller",
+    bindings: &[
+        Binding::both(ActionId::GripPose, "input/grip/pose"),
+        Binding::both(ActionId::AimPose, "input/aim/pose"),
+        Binding::both(ActionId::Haptic, "output/haptic"),
+        Binding::both(ActionId::Trigger, "input/trigger/value"),
+        Binding::both(ActionId::Squeeze, "input/squeeze/value"),
+        Binding::both(ActionId::Thumbstick, "input/thumbstick"),
+        Binding::both(ActionId::ThumbstickClick, "input/thumbstick/click"),
+        Binding::both(ActionId::PrimaryButton, "input/a/click"),
+        Binding::both(ActionId::SecondaryButton, "input/b/click"),
+    ],
+};
+
+/// The Vive wands have a trackpad rather than a thumbstick, and no face buttons.
+pub const HTC_VIVE: InteractionProfile = InteractionProfile {
+    path: "/interaction_profiles/htc/vive_controller",
+    bindings: &[
+        Binding::both(ActionId::GripPose, "input/grip/pose"),
+        Binding::both(ActionId::AimPose, "input/aim/pose"),
+        Binding::both(ActionId::Haptic, "output/haptic"),
+        Binding::both(ActionId::Trigger, "input/trigger/value"),
+        Binding::both(ActionId::Squeeze, "input/squeeze/click"),
+        Binding::both(ActionId::Thumbstick, "input/trackpad"),
+        Binding::both(ActionId::ThumbstickClick, "input/trackpad/click"),
+        Binding::both(ActionId::Menu, "input/menu/click"),
+    ],
+};
+
+/// The Windows Mixed Reality controllers have both a thumbstick and a trackpad, but no face
+/// buttons.
+pub const WINDOWS_MIXED_REALITY: InteractionProfile = InteractionProfile {
+    path: "/interaction_profiles/microsoft/motion_controller",
+    bindings: &[
+        Binding::both(ActionId::GripPose, "input/grip/pose"),
+        Binding::both(ActionId::AimPose, "input/aim/pose"),
+        Binding::both(ActionId::Haptic, "output/haptic"),
+        Binding::both(ActionId::Trigger, "input/trigger/value"),
+        Binding::both(ActionId::Squeeze, "input/squeeze/click"),
+        Binding::both(ActionId::Thumbstick, "input/thumbstick"),
+        Binding::both(ActionId::ThumbstickClick, "input/thumbstick/click"),
+        Binding::both(ActionId::Menu, "input/menu/click"),
+    ],
+};
+
+/// Only available with the `XR_EXT_hp_mixed_reality_controller` extension.
+pub const HP_REVERB_G2: InteractionProfile = InteractionProfile {
+    path: "/interaction_profiles/hp/mixed_reality_controller",
+    bindings: &[
+        Binding::both(ActionId::GripPose, "input/grip/pose"),
+        Binding::both(ActionId::AimPose, "input/aim/pose"),
+        Binding::both(ActionId::Haptic, "output/haptic"),
+        Binding::both(ActionId::Trigger, "input/trigger/value"),
+        Binding::both(ActionId::Squeeze, "input/squeeze/value"),
+        Binding::both(ActionId::Thumbstick, "input/thumbstick"),
+        Binding::both(ActionId::ThumbstickClick, "input/thumbstick/click"),
+        Binding::each(ActionId::PrimaryButton, "input/x/click", "input/a/click"),
+        Binding::each(ActionId::SecondaryButton, "input/y/click", "input/b/click"),
+        Binding::both(ActionId::Menu, "input/menu/click"),
+    ],
+};
+
+/// The profiles that bindings are suggested for. The runtime picks whichever best matches the
+/// controllers in use, falling back to [SIMPLE_CONTROLLER].
+pub const INTERACTION_PROFILES: &[InteractionProfile] = &[
+    SIMPLE_CONTROLLER,
+    OCULUS_TOUCH,
+    VALVE_INDEX,
+    HTC_VIVE,
+    WINDOWS_MIXED_REALITY,
+    HP_REVERB_G2,
+];
+
+/// The state of an action for a hand.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ActionValue {
+    Boolean(bool),
+    Float(f32),
+    Vector2(Vec2),
+    /// The pose in the renderer's world space, or `None` if it isn't tracked.
+    Pose(Option<(Vec3, Quat)>),
+    /// Outputs have no state.
+    Haptic,
+}
+impl ActionValue {
+    /// The state of an inactive action.
+    pub fn inactive(kind: ActionKind) -> Self {
+        match kind {
+            ActionKind::Boolean => Self::Boolean(false),
+            ActionKind::Float => Self::Float(0.0),
+            ActionKind::Vector2 => Self::Vector2(Vec2::ZERO),
+            ActionKind::Pose => Self::Pose(None),
+            ActionKind::Haptic => Self::Haptic,
+        }
+    }
+
+    pub fn kind(&self) -> ActionKind {
+        match self {
+            Self::Boolean(_) => ActionKind::Boolean,
+            Self::Float(_) => ActionKind::Float,
+            Self::Vector2(_) => ActionKind::Vector2,
+            Self::Pose(_) => ActionKind::Pose,
+            Self::Haptic => ActionKind::Haptic,
+        }
+    }
+}
+
+/// The number of joints of a tracked hand, from the palm and wrist to the tip of each finger, in
+/// the order of `XrHandJointEXT`.
+pub const HAND_JOINT_COUNT: usize = 26;
+
+/// The joints of a tracked hand.
+pub type HandJoints = [HandJoint; HAND_JOINT_COUNT];
+
+/// A joint of a tracked hand, in the renderer's world space.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HandJoint {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    /// The distance from the joint to the surface of the hand, in metres.
+    pub radius: f32,
+    /// Whether `translation` is known, whether tracked or inferred.
+    pub position_valid: bool,
+    /// Whether `rotation` is known, whether tracked or inferred.
+    pub orientation_valid: bool,
+}
+
+/// A vibration of a controller.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vibration {
+    /// How long to vibrate for. Zero gives the shortest pulse the controller supports.
+    pub duration: Duration,
+    /// The frequency in Hz, or `None` to leave it to the runtime.
+    pub frequency: Option<f32>,
+    /// The strength, from 0 to 1.
+    pub amplitude: f32,
+}
+
+/// The state of every action for a hand during a frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HandState {
+    values: [ActionValue; ACTIONS.len()],
+}
+impl Default for HandState {
+    fn default() -> Self {
+        Self {
+            values: ACTIONS.map(|action| ActionValue::inactive(action.kind)),
+        }
+    }
+}
+impl HandState {
+    /// How far a float action must be pulled to count as pressed.
+    pub const PRESS_THRESHOLD: f32 = 0.5;
+
+    /// A hand whose controller is at `grip` and points along it, with nothing pressed.
+    pub fn from_grip(grip: Option<(Vec3, Quat)>) -> Self {
+        let mut state = Self::default();
+        state.set(ActionId::GripPose, ActionValue::Pose(grip));
+        state.set(ActionId::AimPose, ActionValue::Pose(grip));
+        state
+    }
+
+    pub fn get(&self, action: ActionId) -> ActionValue {
+        self.values[action as usize]
+    }
+
+    pub fn set(&mut self, action: ActionId, value: ActionValue) {
+        debug_assert_eq!(value.kind(), action.desc().kind, "{action:?}");
+        self.values[action as usize] = value;
+    }
+
+    /// The pose of a pose action, if it is tracked.
+    pub fn pose(&self, action: ActionId) -> Option<(Vec3, Quat)> {
+        match self.get(action) {
+            ActionValue::Pose(pose) => pose,
+            _ => None,
+        }
+    }
+
+    /// The value of a float action, or 0 or 1 for a boolean action.
+    pub fn value(&self, action: ActionId) -> f32 {
+        match self.get(action) {
+            ActionValue::Boolean(pressed) => pressed as u8 as f32,
+            ActionValue::Float(value) => value,
+            _ => 0.0,
+        }
+    }
+
+    /// Whether a boolean action is pressed, or a float action is pulled past
+    /// [Self::PRESS_THRESHOLD].
+    pub fn pressed(&self, action: ActionId) -> bool {
+        self.value(action) >= Self::PRESS_THRESHOLD
+    }
+
+    /// The value of a 2D action, or zero if it isn't one.
+    pub fn vector2(&self, action: ActionId) -> Vec2 {
+        match self.get(action) {
+            ActionValue::Vector2(value) => value,
+            _ => Vec2::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_is_consistent() {
+        for (i, action) in ACTIONS.iter().enumerate() {
+            assert_eq!(action.id as usize, i, "{action:?} is out of order");
+            assert!(
+                action
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-.".contains(c)),
+                "{action:?} has an invalid name"
+            );
+            assert_eq!(
+                ACTIONS.iter().filter(|a| a.name == action.name).count(),
+                1,
+                "{action:?} has a duplicate name"
+            );
+        }
+
+        for profile in INTERACTION_PROFILES {
+            for (i, binding) in profile.bindings.iter().enumerate() {
+                assert!(
+                    profile.bindings[..i]
+                        .iter()
+                        .all(|b| b.action != binding.action),
+                    "{} binds {:?} more than once",
+                    profile.path,
+                    binding.action
+                );
+            }
+            for action in [ActionId::GripPose, ActionId::AimPose] {
+                let binding = profile.bindings.iter().find(|b| b.action == action);
+                assert_eq!(
+                    binding.map(|b| b.paths().count()),
+                    Some(2),
+                    "{} doesn't bind {action:?} for both hands",
+                    profile.path
+                );
+            }
+        }
+        assert_eq!(
+            SIMPLE_CONTROLLER.bindings[0].paths().collect::<Vec<_>>(),
+            [
+                "/user/hand/left/input/grip/pose",
+                "/user/hand/right/input/grip/pose"
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_values_by_kind() {
+        let mut hand = HandState::default();
+        assert!(!hand.pressed(ActionId::Trigger));
+        assert_eq!(hand.pose(ActionId::GripPose), None);
+
+        hand.set(ActionId::Trigger, ActionValue::Float(0.75));
+        hand.set(ActionId::PrimaryButton, ActionValue::Boolean(true));
+        hand.set(
+            ActionId::Thumbstick,
+            ActionValue::Vector2(Vec2::new(0.5, -1.0)),
+        );
+        assert!(hand.pressed(ActionId::Trigger));
+        assert_eq!(hand.value(ActionId::Trigger), 0.75);
+        assert!(hand.pressed(ActionId::PrimaryButton));
+        assert!(!hand.pressed(ActionId::SecondaryButton));
+        assert_eq!(hand.vector2(ActionId::Thumbstick), Vec2::new(0.5, -1.0));
+
+        let grip = (Vec3::ONE, Quat::from_rotation_y(1.0));
+        let hand = HandState::from_grip(Some(grip));
+        assert_eq!(hand.pose(ActionId::GripPose), Some(grip));
+        assert_eq!(hand.pose(ActionId::AimPose), Some(grip));
+    }
+}
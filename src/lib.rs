@@ -0,0 +1,5 @@
+//! The parts of the example that don't need a window or a headset, shared between the renderer
+//! and the `wgsl_preprocess` tool.
+#![deny(missing_docs)]
+
+pub mod wgsl;
@@ -1,42 +1,57 @@
 #![deny(missing_docs)]
 //! wgpu-openxr-example is a barebones example of how to integrate OpenXR with wgpu (Vulkan-only).
 //!
-//! It has four modes:
+//! It has five modes:
 //!   - cargo run --no-default-features: desktop-only, renders the scene without *any* XR integration
 //!   - cargo run -- desktop: build with XR support, but render the scene without initialising XR
 //!   - cargo run -- desktop-with-xr-resolution: build with XR support, initialise XR, but do not render to headset
 //!   - cargo run -- xr: build with XR support, and render to the headset
+//!   - cargo run -- simulated: build with XR support, and render the XR code path with a headset
+//!     simulated by the keyboard and mouse
 //!
 //! These modes are intended to show you how to gracefully integrate XR into your project's code
 //! and how you can move from one stage of integration to the next.
 
-use std::path::Path;
+use std::path::PathBuf;
 
 use anyhow::Context;
 use glam::{vec3, vec4, Quat, Vec3};
 use tracing_subscriber::EnvFilter;
-use wgpu::util::DeviceExt;
+use wgpu_openxr_example::wgsl;
 use winit::{
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{
+        DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent,
+    },
     event_loop::{ControlFlow, EventLoop},
 };
 
+#[cfg(feature = "xr")]
+mod simulated_xr;
 #[cfg(feature = "xr")]
 mod xr;
 
 mod blit_state;
 mod camera;
+mod camera_path;
+mod fly_camera;
+mod frustum;
+// Without XR, the hand states are only used to read and write camera paths.
+#[cfg_attr(not(feature = "xr"), allow(dead_code))]
+mod input;
 mod main_state;
+mod shader_cache;
 mod texture;
 mod types;
-
-pub mod wgsl;
+mod xr_math;
 
 use blit_state::BlitState;
-use camera::CameraState;
-use clap::{command, Parser};
+use camera::{CameraState, CameraUniform, Projection};
+use camera_path::{CameraFrame, CameraPathPlayer, CameraPathRecorder};
+use clap::{command, Parser, Subcommand};
+use fly_camera::FlyCamera;
 use wgpu::{Backends, InstanceDescriptor};
 use main_state::{Instance, MainState};
+use shader_cache::ShaderCache;
 use texture::Texture;
 use types::*;
 
@@ -56,19 +71,79 @@ fn main() -> anyhow::Result<()> {
 
     const MAIN_TRIANGLE_SCALE: f32 = 1.0;
     const HAND_TRIANGLE_SCALE: f32 = 0.1;
+    /// How far the controllers' rays reach when they don't hit anything, in metres.
+    #[cfg(feature = "xr")]
+    const MAX_RAY_LENGTH: f32 = 5.0;
+    /// How fast the left thumbstick moves the camera at full tilt, in metres per second.
+    #[cfg(feature = "xr")]
+    const THUMBSTICK_SPEED: f32 = 2.0;
 
-    #[derive(Parser, PartialEq)]
+    #[derive(Parser)]
     #[command(author, version, about)]
-    enum Args {
+    struct Args {
+        #[cfg(feature = "xr")]
+        #[command(subcommand)]
+        mode: Mode,
+        /// Load shaders from this directory, overriding the shaders embedded in the binary
+        #[arg(long)]
+        shader_dir: Option<PathBuf>,
+        /// Watch the shader directory and rebuild pipelines when it changes
+        #[arg(long, requires = "shader_dir")]
+        watch_shaders: bool,
+        /// Interpupillary distance used for desktop rendering, in millimetres
+        #[arg(long, default_value_t = camera::DEFAULT_IPD * 1_000.0)]
+        ipd: f32,
+        /// Distance at which the desktop eye views converge, in metres [default: parallel views]
+        #[arg(long, value_parser = parse_convergence)]
+        convergence: Option<f32>,
+        /// Render a single view on the desktop instead of a stereo pair
+        #[arg(long)]
+        mono: bool,
+        /// Use an orthographic projection this many metres tall for the desktop camera
+        #[arg(long)]
+        orthographic: Option<f32>,
+        /// Use this projection matrix for the desktop camera, as 16 comma-separated numbers in
+        /// column-major order
+        #[arg(long, value_parser = Projection::parse_custom, conflicts_with = "orthographic")]
+        projection_matrix: Option<Projection>,
+        /// How depth is mapped to the depth buffer
+        #[arg(long, value_enum, default_value_t = DepthMode::ReverseZ)]
+        depth_mode: DepthMode,
+        /// Record the camera and controllers to this file every frame
+        #[arg(long)]
+        record_camera: Option<PathBuf>,
+        /// Play back a file written by `--record-camera` in place of live input, then exit
+        #[arg(long, conflicts_with = "record_camera")]
+        play_camera: Option<PathBuf>,
+        /// Draw the joints of hands tracked by the headset
+        #[cfg(feature = "xr")]
+        #[arg(long)]
+        show_hand_joints: bool,
+    }
+
+    #[cfg(feature = "xr")]
+    #[derive(Subcommand, PartialEq)]
+    enum Mode {
         /// Only desktop
         Desktop,
         /// Desktop with XR initialization and resolution
         DesktopWithXrResolution,
         /// Render to headset
         Xr,
+        /// Render the XR code path with a headset and controllers simulated by the keyboard and
+        /// mouse
+        Simulated,
     }
 
-    #[cfg(feature = "xr")]
+    /// How often to check the shader directory for changes when watching it.
+    const SHADER_WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+    /// How much the `[` and `]` keys change the IPD by, in metres.
+    const IPD_STEP: f32 = 1.0 / 1_000.0;
+    /// The factor by which `,` and `.` change the convergence distance.
+    const CONVERGENCE_STEP: f32 = 1.25;
+    /// Convergence distances beyond this, in metres, are treated as parallel views.
+    const MAX_CONVERGENCE_DISTANCE: f32 = 20.0;
+
     let args = Args::parse();
 
     let wgpu_features = wgpu::Features::MULTIVIEW | wgpu::Features::PUSH_CONSTANTS;
@@ -81,14 +156,13 @@ fn main() -> anyhow::Result<()> {
     let window = winit::window::Window::new(&event_loop)?;
 
     #[cfg(feature = "xr")]
-    let (wgpu_state, surface, mut xr_state) = if args != Args::Desktop {
+    let initialize_xr = matches!(args.mode, Mode::DesktopWithXrResolution | Mode::Xr);
+    #[cfg(feature = "xr")]
+    let (wgpu_state, surface, mut xr_state) = if initialize_xr {
         let (wgpu_state, xr_state) = xr::XrState::initialize_with_wgpu(wgpu_features, wgpu_limits)?;
         window.set_resizable(false);
-        let view = xr_state.views()[0];
-        window.set_inner_size(winit::dpi::PhysicalSize::new(
-            view.recommended_image_rect_width,
-            view.recommended_image_rect_height,
-        ));
+        let (width, height) = xr_state.resolution();
+        window.set_inner_size(winit::dpi::PhysicalSize::new(width, height));
         let surface = unsafe { wgpu_state.instance.create_surface(&window) }?;
         (wgpu_state, surface, Some(xr_state))
     } else {
@@ -99,16 +173,57 @@ fn main() -> anyhow::Result<()> {
     #[cfg(not(feature = "xr"))]
     let (wgpu_state, surface) = create_wgpu_state(&window, wgpu_features, wgpu_limits)?;
 
-    let mut camera_state = CameraState::new(&wgpu_state.device, window.inner_size());
+    // Without a headset, real or simulated, the render target only needs as many layers as the
+    // desktop camera has views.
+    #[cfg(feature = "xr")]
+    let desktop_only = xr_state.is_none() && args.mode != Mode::Simulated;
+    #[cfg(not(feature = "xr"))]
+    let desktop_only = true;
+    #[cfg(feature = "xr")]
+    let view_count = xr_state
+        .as_ref()
+        .map_or(DESKTOP_VIEW_COUNT, xr::XrState::view_count);
+    #[cfg(not(feature = "xr"))]
+    let view_count = DESKTOP_VIEW_COUNT;
+    let mut view_count = if desktop_only && args.mono {
+        1
+    } else {
+        view_count
+    };
+
+    let mut camera_state = CameraState::new(&wgpu_state.device, window.inner_size(), view_count);
+    camera_state.data.ipd = args.ipd / 1_000.0;
+    camera_state.data.convergence_distance = args.convergence.map_or(f32::INFINITY, |distance| {
+        distance.max(camera::MIN_CONVERGENCE_DISTANCE)
+    });
+    camera_state.data.mono = args.mono;
+    camera_state.data.depth_mode = args.depth_mode;
+    if let Some(height) = args.orthographic {
+        camera_state.data.projection = Projection::Orthographic { height };
+    }
+    if let Some(projection) = args.projection_matrix {
+        camera_state.data.projection = projection;
+    }
+    let mut fly_camera = FlyCamera::new(&camera_state.data);
+    #[cfg(feature = "xr")]
+    let mut simulated_headset =
+        (args.mode == Mode::Simulated).then(simulated_xr::SimulatedHeadset::new);
 
-    let preprocessor = wgsl::Preprocessor::from_directory(Path::new("shaders"))?;
+    let mut preprocessor = wgsl::Preprocessor::embedded(args.shader_dir.as_deref())?;
+    let mut shader_cache = ShaderCache::new();
 
     let window_swapchain_format = surface.get_capabilities(&wgpu_state.adapter).formats[0];
     let mut main_state = MainState::new(
         &wgpu_state.device,
         &preprocessor,
+        &mut shader_cache,
         &camera_state,
         window_swapchain_format,
+        &[
+            Vertex::new(vec3(-1.0, -1.0, 0.0), vec4(1.0, 0.0, 0.0, 1.0)),
+            Vertex::new(vec3(0.0, 1.0, 0.0), vec4(0.0, 1.0, 0.0, 1.0)),
+            Vertex::new(vec3(1.0, -1.0, 0.0), vec4(0.0, 0.0, 1.0, 1.0)),
+        ],
         vec![
             Instance::new(
                 vec3(0.0, 0.0, 1.0),
@@ -141,36 +256,49 @@ fn main() -> anyhow::Result<()> {
         }
     };
     surface.configure(&wgpu_state.device, &config);
-    let mut depth_texture = Texture::new_depth_texture(&wgpu_state.device, &config);
-    let mut rt_texture =
-        Texture::new_rt_texture(&wgpu_state.device, &config, window_swapchain_format);
+    let mut depth_texture = Texture::new_depth_texture(&wgpu_state.device, &config, view_count);
+    let mut rt_texture = Texture::new_rt_texture(
+        &wgpu_state.device,
+        &config,
+        window_swapchain_format,
+        view_count,
+    );
     let mut blit_state = BlitState::new(
         &wgpu_state.device,
         &preprocessor,
+        &mut shader_cache,
         rt_texture.view(),
         window_swapchain_format,
         #[cfg(not(feature = "xr"))]
         window_swapchain_format,
         #[cfg(feature = "xr")]
         xr::WGPU_COLOR_FORMAT,
+        view_count,
     );
-
-    let triangle_vertex_buffer =
-        wgpu_state
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(&[
-                    Vertex::new(vec3(-1.0, -1.0, 0.0), vec4(1.0, 0.0, 0.0, 1.0)),
-                    Vertex::new(vec3(0.0, 1.0, 0.0), vec4(0.0, 1.0, 0.0, 1.0)),
-                    Vertex::new(vec3(1.0, -1.0, 0.0), vec4(0.0, 0.0, 1.0, 1.0)),
-                ]),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
+    log::info!("shader cache: {}", shader_cache.stats());
+
+    let mut camera_recorder = args
+        .record_camera
+        .as_deref()
+        .map(CameraPathRecorder::create)
+        .transpose()?;
+    let mut camera_player = args
+        .play_camera
+        .as_deref()
+        .map(CameraPathPlayer::load)
+        .transpose()?;
 
     let start_time = std::time::Instant::now();
     let (mut fps_timer, mut fps_count) = (std::time::Instant::now(), 0);
+    let mut frame_timer = std::time::Instant::now();
     let mut view_index = 0;
+    #[cfg(feature = "xr")]
+    let mut triggers_pressed = [false; 2];
+    #[cfg(feature = "xr")]
+    let mut aim_hits = [None; 2];
+    let shader_updates = args
+        .watch_shaders
+        .then(|| watch_shaders(preprocessor.clone(), SHADER_WATCH_INTERVAL));
     event_loop.run(move |event, _, control_flow| {
         // Have the closure take ownership of the resources.
         // `event_loop.run` never returns, therefore we must do this to ensure
@@ -179,7 +307,6 @@ fn main() -> anyhow::Result<()> {
         let _ = &xr_state;
         let _ = (
             &wgpu_state,
-            &triangle_vertex_buffer,
             &main_state,
             &depth_texture,
             &rt_texture,
@@ -198,9 +325,14 @@ fn main() -> anyhow::Result<()> {
                 config.width = size.width;
                 config.height = size.height;
                 surface.configure(&wgpu_state.device, &config);
-                depth_texture = Texture::new_depth_texture(&wgpu_state.device, &config);
-                rt_texture =
-                    Texture::new_rt_texture(&wgpu_state.device, &config, window_swapchain_format);
+                depth_texture =
+                    Texture::new_depth_texture(&wgpu_state.device, &config, view_count);
+                rt_texture = Texture::new_rt_texture(
+                    &wgpu_state.device,
+                    &config,
+                    window_swapchain_format,
+                    view_count,
+                );
 
                 blit_state.resize(&wgpu_state.device, rt_texture.view());
                 camera_state.data.resize(size);
@@ -225,7 +357,74 @@ fn main() -> anyhow::Result<()> {
                     },
                 ..
             } => {
-                view_index = (view_index + 1) % 2;
+                view_index = (view_index + 1) % view_count;
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode:
+                                    Some(
+                                        key @ (VirtualKeyCode::M
+                                        | VirtualKeyCode::LBracket
+                                        | VirtualKeyCode::RBracket
+                                        | VirtualKeyCode::Comma
+                                        | VirtualKeyCode::Period),
+                                    ),
+                                state: ElementState::Released,
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let camera = &mut camera_state.data;
+                match key {
+                    VirtualKeyCode::M => camera.mono = !camera.mono,
+                    VirtualKeyCode::LBracket => camera.ipd = (camera.ipd - IPD_STEP).max(0.0),
+                    VirtualKeyCode::RBracket => camera.ipd += IPD_STEP,
+                    VirtualKeyCode::Comma => {
+                        camera.convergence_distance = (camera
+                            .convergence_distance
+                            .min(MAX_CONVERGENCE_DISTANCE)
+                            / CONVERGENCE_STEP)
+                            .max(camera::MIN_CONVERGENCE_DISTANCE);
+                    }
+                    _ => {
+                        camera.convergence_distance *= CONVERGENCE_STEP;
+                        if camera.convergence_distance > MAX_CONVERGENCE_DISTANCE {
+                            camera.convergence_distance = f32::INFINITY;
+                        }
+                    }
+                }
+
+                if key == VirtualKeyCode::M && desktop_only {
+                    let new_view_count = camera_state.data.view_count();
+                    let rebuilt = main_state.set_view_count(
+                        &wgpu_state.device,
+                        &preprocessor,
+                        &mut shader_cache,
+                        new_view_count,
+                    );
+                    if let Err(e) = rebuilt {
+                        log::error!("failed to rebuild pipelines for {new_view_count} views: {e:#}");
+                        camera_state.data.mono = !camera_state.data.mono;
+                        return;
+                    }
+                    view_count = new_view_count;
+                    view_index %= view_count;
+                    camera_state.set_view_count(&wgpu_state.device, view_count);
+                    depth_texture =
+                        Texture::new_depth_texture(&wgpu_state.device, &config, view_count);
+                    rt_texture = Texture::new_rt_texture(
+                        &wgpu_state.device,
+                        &config,
+                        window_swapchain_format,
+                        view_count,
+                    );
+                    blit_state.resize(&wgpu_state.device, rt_texture.view());
+                }
             }
             Event::WindowEvent {
                 event:
@@ -240,7 +439,91 @@ fn main() -> anyhow::Result<()> {
                     },
                 ..
             } => {
-                *control_flow = ControlFlow::Exit;
+                // The first press releases the cursor if it's grabbed for mouse look.
+                if fly_camera.grabbed() {
+                    fly_camera.set_grabbed(&window, false);
+                } else {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(key),
+                                state,
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                fly_camera.handle_key(key, state);
+                #[cfg(feature = "xr")]
+                if let Some(simulated_headset) = &mut simulated_headset {
+                    simulated_headset.handle_key(key, state);
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::ModifiersChanged(modifiers),
+                ..
+            } => fly_camera.handle_modifiers(modifiers),
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    },
+                ..
+            } => fly_camera.set_grabbed(&window, true),
+            #[cfg(feature = "xr")]
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Right,
+                        ..
+                    },
+                ..
+            } => {
+                if let Some(simulated_headset) = &mut simulated_headset {
+                    simulated_headset.set_dragging(state == ElementState::Pressed);
+                }
+            }
+            #[cfg(feature = "xr")]
+            Event::WindowEvent {
+                event: WindowEvent::MouseWheel { delta, .. },
+                ..
+            } => {
+                if let Some(simulated_headset) = &mut simulated_headset {
+                    simulated_headset.handle_scroll(delta);
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Focused(false),
+                ..
+            } => {
+                fly_camera.set_grabbed(&window, false);
+                fly_camera.release_all();
+            }
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                #[cfg(feature = "xr")]
+                if let Some(simulated_headset) = simulated_headset.as_mut().filter(|s| s.dragging())
+                {
+                    simulated_headset.handle_mouse_motion(delta);
+                    return;
+                }
+                fly_camera.handle_mouse_motion(delta);
+            }
+            Event::LoopDestroyed => {
+                if let Some(Err(e)) = camera_recorder.as_mut().map(CameraPathRecorder::flush) {
+                    log::error!("failed to write camera path: {e}");
+                }
             }
             Event::MainEventsCleared => {
                 window.request_redraw();
@@ -253,8 +536,34 @@ fn main() -> anyhow::Result<()> {
             return;
         }
 
+        let dt = frame_timer.elapsed().as_secs_f32();
+        frame_timer = std::time::Instant::now();
+        #[cfg(feature = "xr")]
+        if let Some(simulated_headset) = &mut simulated_headset {
+            simulated_headset.update(&fly_camera, dt);
+        } else {
+            fly_camera.update(&mut camera_state.data, dt);
+        }
+        #[cfg(not(feature = "xr"))]
+        fly_camera.update(&mut camera_state.data, dt);
+
+        // Only the latest change matters if several arrived since the last frame.
+        if let Some(reloaded) = shader_updates.iter().flat_map(|r| r.try_iter()).last() {
+            preprocessor = reloaded;
+            log::info!("shaders changed, rebuilding pipelines");
+            let reloaded = main_state
+                .reload_shaders(&wgpu_state.device, &preprocessor, &mut shader_cache)
+                .and_then(|_| {
+                    blit_state.reload_shaders(&wgpu_state.device, &preprocessor, &mut shader_cache)
+                });
+            if let Err(e) = reloaded {
+                log::error!("failed to reload shaders, keeping old pipelines: {e:#}");
+            }
+            log::info!("shader cache: {}", shader_cache.stats());
+        }
+
         #[cfg(feature = "xr")]
-        let xr_frame_state = if args == Args::Xr {
+        let xr_frame_state = if args.mode == Mode::Xr {
             xr_state.as_mut().and_then(|x| x.pre_frame().unwrap())
         } else {
             None
@@ -263,13 +572,6 @@ fn main() -> anyhow::Result<()> {
         let mut encoder = wgpu_state
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        main_state.encode_draw_pass(
-            &mut encoder,
-            rt_texture.view(),
-            depth_texture.view(),
-            &triangle_vertex_buffer,
-            camera_state.bind_group(),
-        );
 
         let frame = surface
             .get_current_texture()
@@ -277,10 +579,15 @@ fn main() -> anyhow::Result<()> {
         let view = frame
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
-        blit_state.encode_draw_pass(&mut encoder, &view, Some(view_index));
+        let window_view_index = if camera_state.data.mono {
+            0
+        } else {
+            view_index
+        };
+        blit_state.encode_draw_pass(&mut encoder, &view, Some(window_view_index));
 
         #[cfg(feature = "xr")]
-        let pfd = xr_state
+        let mut pfd = xr_state
             .as_mut()
             .zip(xr_frame_state)
             .map(|(xr_state, xr_frame_state)| {
@@ -292,41 +599,219 @@ fn main() -> anyhow::Result<()> {
                         &blit_state,
                     )
                     .unwrap()
+            })
+            .or_else(|| {
+                simulated_headset
+                    .as_ref()
+                    .map(|simulated_headset| simulated_headset.post_frame(&camera_state.data))
             });
 
-        let time_since_start = start_time.elapsed().as_secs_f32();
+        // Move through the scene with the left thumbstick, along the floor in the direction the
+        // headset is facing.
+        #[cfg(feature = "xr")]
+        if let Some(pfd) = &pfd {
+            if let Some(view) = pfd.views.first() {
+                let stick = pfd
+                    .hand(input::Hand::Left)
+                    .vector2(input::ActionId::Thumbstick);
+                let (_, rotation) = xr::openxr_pose_to_glam(&view.pose);
+                let facing = rotation * Vec3::Z;
+                let forward = vec3(facing.x, 0.0, facing.z).normalize_or_zero();
+                let right = forward.cross(Vec3::Y);
+                camera_state.data.eye +=
+                    (forward * stick.y + right * stick.x) * THUMBSTICK_SPEED * dt;
+            }
+        }
+
+        let mut time_since_start = start_time.elapsed().as_secs_f32();
+        // Recorded views replace the headset's for rendering, but the headset's own views are
+        // still the ones submitted with the frame, as the runtime expects the poses and view
+        // count it located for this frame.
+        #[cfg(feature = "xr")]
+        let mut played_views: Option<Vec<openxr::View>> = None;
+        if let Some(camera_player) = &mut camera_player {
+            if let Some(camera_frame) = camera_player.next_frame() {
+                time_since_start = camera_frame.time;
+                camera_state.data.eye = camera_frame.eye;
+                camera_state.data.target = camera_frame.target;
+                #[cfg(feature = "xr")]
+                if !camera_frame.views.is_empty() {
+                    let views = camera_frame.views.iter().map(Into::into).collect();
+                    let hands = camera_frame.hands.clone();
+                    match &mut pfd {
+                        Some(pfd) => {
+                            played_views = Some(views);
+                            pfd.hands = hands;
+                            pfd.hand_joints = Default::default();
+                        }
+                        None => {
+                            pfd = Some(xr::PostFrameData {
+                                views,
+                                hands,
+                                hand_joints: Default::default(),
+                            })
+                        }
+                    }
+                }
+            } else {
+                let elapsed = camera_player.elapsed();
+                log::info!(
+                    "played back {} frames in {:.02?} ({:.02} FPS)",
+                    camera_player.frames_played(),
+                    elapsed,
+                    camera_player.frames_played() as f32 / elapsed.as_secs_f32(),
+                );
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+        if let Some(recorder) = &mut camera_recorder {
+            #[cfg(feature = "xr")]
+            let (views, hands) = pfd.as_ref().map_or_else(Default::default, |pfd| {
+                (
+                    pfd.views.iter().map(Into::into).collect(),
+                    pfd.hands.clone(),
+                )
+            });
+            #[cfg(not(feature = "xr"))]
+            let (views, hands) = Default::default();
+
+            let camera_frame = CameraFrame {
+                time: time_since_start,
+                eye: camera_state.data.eye,
+                target: camera_state.data.target,
+                views,
+                hands,
+            };
+            if let Err(e) = recorder.record(&camera_frame) {
+                log::error!("failed to record camera path, stopping recording: {e}");
+                camera_recorder = None;
+            }
+        }
         {
             let insts = &mut main_state.instances;
             insts[0].rotation = Quat::from_rotation_y(time_since_start / std::f32::consts::PI);
             #[cfg(feature = "xr")]
             if let Some(pfd) = &pfd {
-                if let Some(lh) = pfd.left_hand {
-                    (insts[1].translation, insts[1].rotation) = lh;
+                for (inst, hand) in insts[1..].iter_mut().zip(&pfd.hands) {
+                    if let Some(grip) = hand.pose(input::ActionId::GripPose) {
+                        (inst.translation, inst.rotation) = grip;
+                    }
+                    // Pulling the trigger grows the controller's triangle, up to twice its size.
+                    inst.scale = Vec3::ONE
+                        * HAND_TRIANGLE_SCALE
+                        * (1.0 + hand.value(input::ActionId::Trigger));
                 }
-                if let Some(rh) = pfd.right_hand {
-                    (insts[2].translation, insts[2].rotation) = rh;
+            }
+        }
+
+        // Point a ray from each controller and report what it hits.
+        #[cfg(feature = "xr")]
+        {
+            let mut lines = vec![];
+            aim_hits = [None; 2];
+            for (index, hand) in pfd.iter().flat_map(|pfd| &pfd.hands).enumerate() {
+                if let Some((origin, rotation)) = hand.pose(input::ActionId::AimPose) {
+                    let ray = frustum::Ray {
+                        origin,
+                        direction: rotation * Vec3::Z,
+                    };
+                    // The controller's own triangle surrounds its aim pose.
+                    aim_hits[index] = main_state.ray_cast(ray, &[1 + index]);
+                    let (length, color) = match aim_hits[index] {
+                        Some(hit) => (hit.distance, vec4(0.0, 1.0, 0.0, 1.0)),
+                        None => (MAX_RAY_LENGTH, vec4(1.0, 1.0, 1.0, 1.0)),
+                    };
+                    lines.push([
+                        Vertex::new(origin, color),
+                        Vertex::new(origin + ray.direction * length, color),
+                    ]);
                 }
             }
+            main_state.upload_lines(&wgpu_state.queue, &lines);
         }
-        main_state.upload_instances(&wgpu_state.queue);
 
+        #[cfg(feature = "xr")]
+        if args.show_hand_joints {
+            let joints: Vec<_> = pfd
+                .iter()
+                .flat_map(|pfd| pfd.hand_joints.iter().flatten().flatten())
+                .filter(|joint| joint.position_valid && joint.orientation_valid)
+                .map(|joint| {
+                    Instance::new(joint.translation, joint.rotation, Vec3::splat(joint.radius))
+                })
+                .collect();
+            main_state.upload_debug_instances(&wgpu_state.queue, &joints);
+        }
+
+        // Vibrate each controller while its trigger is pressed.
+        #[cfg(feature = "xr")]
+        if let (Some(xr_state), Some(pfd)) = (&xr_state, &pfd) {
+            for hand in input::Hand::ALL {
+                let pressed = pfd.hand(hand).pressed(input::ActionId::Trigger);
+                if pressed == std::mem::replace(&mut triggers_pressed[hand as usize], pressed) {
+                    continue;
+                }
+                let result = if pressed {
+                    xr_state.apply_haptic_feedback(
+                        hand,
+                        input::Vibration {
+                            duration: std::time::Duration::from_secs(1),
+                            frequency: None,
+                            amplitude: 0.5,
+                        },
+                    )
+                } else {
+                    xr_state.stop_haptic_feedback(hand)
+                };
+                if let Err(e) = result {
+                    log::warn!("failed to update haptic feedback: {e}");
+                }
+            }
+        }
+
+        let mut camera_uniforms = {
+            #[cfg(feature = "xr")]
+            match played_views
+                .as_deref()
+                .or_else(|| pfd.as_ref().map(|pfd| &pfd.views[..]))
+            {
+                // The runtime reports no views for frames it doesn't want rendered.
+                Some(views) if !views.is_empty() => {
+                    camera_state.data.to_view_uniforms_with_xr_views(views)
+                }
+                _ => camera_state.data.to_view_uniforms(),
+            }
+            #[cfg(not(feature = "xr"))]
+            camera_state.data.to_view_uniforms()
+        };
+        // The desktop camera's views don't have to match the target's, e.g. a pair for a headset
+        // with quad views, so drop the extra views or repeat the last one to fill every layer.
+        if let Some(&last_uniform) = camera_uniforms.last() {
+            camera_uniforms.resize(view_count as usize, last_uniform);
+        }
+        let frusta: Vec<_> = camera_uniforms.iter().map(CameraUniform::frustum).collect();
+        main_state.upload_instances(&wgpu_state.queue, &frusta);
         wgpu_state.queue.write_buffer(
             camera_state.buffer(),
             0,
-            bytemuck::cast_slice(&{
-                #[cfg(feature = "xr")]
-                match &pfd {
-                    Some(pfd) => camera_state
-                        .data
-                        .to_view_proj_matrices_with_xr_views(&pfd.views),
-                    None => camera_state.data.to_view_proj_matrices(),
-                }
-                #[cfg(not(feature = "xr"))]
-                camera_state.data.to_view_proj_matrices()
-            }),
+            bytemuck::cast_slice(&camera_uniforms),
         );
 
-        wgpu_state.queue.submit(Some(encoder.finish()));
+        // The scene can only be encoded once the views it's culled against are known, which is
+        // after `post_frame` in XR, but it must be submitted before the blits that read it.
+        let mut scene_encoder = wgpu_state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        main_state.encode_draw_pass(
+            &mut scene_encoder,
+            rt_texture.view(),
+            depth_texture.view(),
+            camera_state.bind_group(),
+        );
+
+        wgpu_state
+            .queue
+            .submit([scene_encoder.finish(), encoder.finish()]);
 
         #[cfg(feature = "xr")]
         if let (Some(xr_state), Some(xr_frame_state), Some(pfd)) =
@@ -343,10 +828,28 @@ fn main() -> anyhow::Result<()> {
 
         fps_count += 1;
         if fps_timer.elapsed().as_millis() > 100 {
+            let camera = &camera_state.data;
+            #[cfg(feature = "xr")]
+            let aim = ["left", "right"]
+                .iter()
+                .zip(&aim_hits)
+                .filter_map(|(hand, hit)| Some(format!(" | {hand} aim: {}", hit.as_ref()?)))
+                .collect::<String>();
+            #[cfg(not(feature = "xr"))]
+            let aim = "";
             window.set_title(&format!(
-                "wgpu-openxr-example: {:.02} FPS | {} view",
+                "wgpu-openxr-example: {:.02} FPS | {} view | IPD {:.0} mm, convergence {:.2} m | {}{}",
                 (fps_count as f32) / fps_timer.elapsed().as_secs_f32(),
-                if view_index == 0 { "left" } else { "right" }
+                match (camera.mono, view_count, view_index) {
+                    (true, ..) | (false, 1, _) => "mono".to_string(),
+                    (false, 2, 0) => "left".to_string(),
+                    (false, 2, _) => "right".to_string(),
+                    (false, _, index) => format!("#{index}"),
+                },
+                camera.ipd * 1_000.0,
+                camera.convergence_distance,
+                main_state.cull_stats(),
+                aim,
             ));
 
             fps_count = 0;
@@ -355,6 +858,35 @@ fn main() -> anyhow::Result<()> {
     });
 }
 
+/// Re-reads the shader files on another thread every `interval`, so that the render loop isn't
+/// held up by the file system, and sends the preprocessor whenever they have changed.
+fn watch_shaders(
+    mut preprocessor: wgsl::Preprocessor,
+    interval: std::time::Duration,
+) -> std::sync::mpsc::Receiver<wgsl::Preprocessor> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        match preprocessor.reload() {
+            Ok(true) => {
+                if sender.send(preprocessor.clone()).is_err() {
+                    break;
+                }
+            }
+            Ok(false) => {}
+            Err(e) => log::error!("failed to read shaders: {e}"),
+        }
+    });
+    receiver
+}
+
+/// Parses a `--convergence` distance, which must be positive.
+fn parse_convergence(source: &str) -> anyhow::Result<f32> {
+    let distance: f32 = source.parse()?;
+    anyhow::ensure!(distance > 0.0, "the convergence distance must be positive");
+    Ok(distance)
+}
+
 fn create_wgpu_state(
     window: &winit::window::Window,
     wgpu_features: wgpu::Features,
@@ -1,10 +1,13 @@
 use glam::{Mat4, Quat, Vec3};
-use std::{borrow::Cow, num::NonZeroU32};
+use std::{num::NonZeroU32, rc::Rc};
 use wgpu::util::DeviceExt;
 
 use crate::{
     camera::CameraState,
-    types::{Vertex, DEPTH_FORMAT, VIEW_COUNT},
+    frustum::{self, BoundingSphere, Frustum, Ray},
+    shader_cache::{PipelineState, ShaderCache},
+    types::{DepthMode, Vertex, DEPTH_FORMAT},
+    wgsl::{Defines, Preprocessor},
 };
 
 pub struct Instance {
@@ -21,6 +24,14 @@ impl Instance {
         }
     }
 
+    /// The bounds of the instance's mesh, given the bounds of the mesh itself.
+    pub fn bounding_sphere(&self, mesh_bounds: BoundingSphere) -> BoundingSphere {
+        BoundingSphere {
+            center: self.translation + self.rotation * (self.scale * mesh_bounds.center),
+            radius: mesh_bounds.radius * self.scale.abs().max_element(),
+        }
+    }
+
     fn to_cols_array(&self) -> [f32; 16] {
         Mat4::from(glam::Affine3A::from_scale_rotation_translation(
             self.scale,
@@ -31,28 +42,191 @@ impl Instance {
     }
 }
 
+/// How many instances were drawn and culled by the last [MainState::upload_instances].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CullStats {
+    pub drawn: usize,
+    pub culled: usize,
+}
+impl std::fmt::Display for CullStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} drawn, {} culled", self.drawn, self.culled)
+    }
+}
+
+/// The nearest instance hit by [MainState::ray_cast].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+    /// The index of the instance in [MainState::instances].
+    pub instance: usize,
+    /// The distance along the ray to the instance's bounds.
+    pub distance: f32,
+}
+impl std::fmt::Display for RayHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "instance {} at {:.2} m", self.instance, self.distance)
+    }
+}
+
 pub struct MainState {
-    #[allow(dead_code)]
-    shader: wgpu::ShaderModule,
-    #[allow(dead_code)]
-    pipeline_layout: wgpu::PipelineLayout,
-    pipeline: wgpu::RenderPipeline,
+    pipeline_layout: Rc<wgpu::PipelineLayout>,
+    swapchain_format: wgpu::TextureFormat,
+    depth_mode: DepthMode,
+    view_count: u32,
+    pipeline: Rc<wgpu::RenderPipeline>,
+    line_pipeline: Rc<wgpu::RenderPipeline>,
     pub instances: Vec<Instance>,
+    /// The mesh drawn for each instance.
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    /// The bounds of the mesh drawn for each instance.
+    mesh_bounds: BoundingSphere,
     instance_buffer: wgpu::Buffer,
+    cull_stats: CullStats,
+    debug_instance_buffer: wgpu::Buffer,
+    debug_instance_count: u32,
+    line_vertex_buffer: wgpu::Buffer,
+    line_count: u32,
 }
 impl MainState {
+    /// The most instances [Self::upload_debug_instances] can draw: enough for every joint of both
+    /// hands.
+    pub const MAX_DEBUG_INSTANCES: usize = 64;
+    /// The most lines [Self::upload_lines] can draw.
+    pub const MAX_LINES: usize = 16;
+
+    /// Creates the state for drawing `instances` of the mesh made of `vertices`, with the
+    /// camera's view count and depth mode.
     pub fn new(
         device: &wgpu::Device,
-        preprocessor: &crate::wgsl::Preprocessor,
+        preprocessor: &Preprocessor,
+        shader_cache: &mut ShaderCache,
         camera_state: &CameraState,
         swapchain_format: wgpu::TextureFormat,
+        vertices: &[Vertex],
         instances: Vec<Instance>,
     ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let positions: Vec<_> = vertices.iter().map(Vertex::position).collect();
+        let mesh_bounds = BoundingSphere::enclosing(&positions);
         let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Index Buffer"),
             contents: bytemuck::cast_slice(&Self::instances_to_data(&instances)),
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         });
+        let debug_instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug Instance Buffer"),
+            size: (std::mem::size_of::<[f32; 16]>() * Self::MAX_DEBUG_INSTANCES) as _,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let line_vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Line Vertex Buffer"),
+            size: (std::mem::size_of::<[Vertex; 2]>() * Self::MAX_LINES) as _,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let pipeline_layout = Rc::new(device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[(camera_state.bind_group_layout())],
+                push_constant_ranges: &[],
+            },
+        ));
+        let view_count = camera_state.view_count();
+        let depth_mode = camera_state.data.depth_mode;
+        let (pipeline, line_pipeline) = Self::create_pipelines(
+            device,
+            preprocessor,
+            shader_cache,
+            &pipeline_layout,
+            swapchain_format,
+            depth_mode,
+            view_count,
+        )
+        .unwrap();
+        let cull_stats = CullStats {
+            drawn: instances.len(),
+            culled: 0,
+        };
+        Self {
+            pipeline_layout,
+            swapchain_format,
+            depth_mode,
+            view_count,
+            pipeline,
+            line_pipeline,
+
+            instances,
+            vertex_buffer,
+            vertex_count: vertices.len() as u32,
+            mesh_bounds,
+            instance_buffer,
+            cull_stats,
+            debug_instance_buffer,
+            debug_instance_count: 0,
+            line_vertex_buffer,
+            line_count: 0,
+        }
+    }
+
+    /// Rebuilds the pipelines from the current files in `preprocessor`. If a new shader fails
+    /// to compile, the existing pipelines are kept and the error is returned.
+    pub fn reload_shaders(
+        &mut self,
+        device: &wgpu::Device,
+        preprocessor: &Preprocessor,
+        shader_cache: &mut ShaderCache,
+    ) -> anyhow::Result<()> {
+        (self.pipeline, self.line_pipeline) = Self::create_pipelines(
+            device,
+            preprocessor,
+            shader_cache,
+            &self.pipeline_layout,
+            self.swapchain_format,
+            self.depth_mode,
+            self.view_count,
+        )?;
+        Ok(())
+    }
+
+    /// Rebuilds the pipelines to draw `view_count` views, which must match the camera's bind
+    /// group and the render target. If the new pipelines fail to compile, the existing ones are
+    /// kept and the error is returned.
+    pub fn set_view_count(
+        &mut self,
+        device: &wgpu::Device,
+        preprocessor: &Preprocessor,
+        shader_cache: &mut ShaderCache,
+        view_count: u32,
+    ) -> anyhow::Result<()> {
+        (self.pipeline, self.line_pipeline) = Self::create_pipelines(
+            device,
+            preprocessor,
+            shader_cache,
+            &self.pipeline_layout,
+            self.swapchain_format,
+            self.depth_mode,
+            view_count,
+        )?;
+        self.view_count = view_count;
+        Ok(())
+    }
+
+    /// Creates the pipelines for the instances and for lines.
+    fn create_pipelines(
+        device: &wgpu::Device,
+        preprocessor: &Preprocessor,
+        shader_cache: &mut ShaderCache,
+        pipeline_layout: &Rc<wgpu::PipelineLayout>,
+        swapchain_format: wgpu::TextureFormat,
+        depth_mode: DepthMode,
+        view_count: u32,
+    ) -> anyhow::Result<(Rc<wgpu::RenderPipeline>, Rc<wgpu::RenderPipeline>)> {
         let instance_buffer_layout = wgpu::VertexBufferLayout {
             array_stride: (std::mem::size_of::<f32>() * 4 * 4) as _,
             step_mode: wgpu::VertexStepMode::Instance,
@@ -66,12 +240,6 @@ impl MainState {
                 .collect::<Vec<_>>(),
         };
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Owned(
-                preprocessor.preprocess("main.wgsl").unwrap(),
-            )),
-        });
         let vertex_buffer_layout = wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as _,
             step_mode: wgpu::VertexStepMode::Vertex,
@@ -88,55 +256,162 @@ impl MainState {
                 },
             ],
         };
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[(camera_state.bind_group_layout())],
-            push_constant_ranges: &[],
-        });
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[vertex_buffer_layout, instance_buffer_layout],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(swapchain_format.into())],
-            }),
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: NonZeroU32::new(VIEW_COUNT),
-        });
-        Self {
-            shader,
-            pipeline_layout,
-            pipeline,
+        let depth_stencil = wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: depth_mode.compare_function(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        };
+        let defines = Defines::new().with("VIEW_COUNT", view_count);
+        let state = PipelineState {
+            layout: pipeline_layout,
+            format: swapchain_format,
+            depth_mode: Some(depth_mode),
+            multiview: NonZeroU32::new(view_count),
+            topology: wgpu::PrimitiveTopology::TriangleList,
+        };
 
-            instances,
-            instance_buffer,
-        }
+        let pipeline = shader_cache.pipeline(
+            device,
+            preprocessor,
+            "main.wgsl",
+            &defines,
+            state,
+            |shader| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("main"),
+                    layout: Some(state.layout),
+                    vertex: wgpu::VertexState {
+                        module: shader,
+                        entry_point: "vs_main",
+                        buffers: &[vertex_buffer_layout.clone(), instance_buffer_layout],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(state.format.into())],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: state.topology,
+                        ..Default::default()
+                    },
+                    depth_stencil: Some(depth_stencil.clone()),
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: state.multiview,
+                })
+            },
+        )?;
+        let line_state = PipelineState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..state
+        };
+        let line_pipeline = shader_cache.pipeline(
+            device,
+            preprocessor,
+            "line.wgsl",
+            &defines,
+            line_state,
+            |line_shader| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("line"),
+                    layout: Some(line_state.layout),
+                    vertex: wgpu::VertexState {
+                        module: line_shader,
+                        entry_point: "vs_main",
+                        buffers: &[vertex_buffer_layout],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: line_shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(line_state.format.into())],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: line_state.topology,
+                        ..Default::default()
+                    },
+                    depth_stencil: Some(depth_stencil),
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: line_state.multiview,
+                })
+            },
+        )?;
+        Ok((pipeline, line_pipeline))
     }
 
-    pub fn upload_instances(&self, queue: &wgpu::Queue) {
+    /// Uploads the instances that are visible from any of `frusta`; the rest are not drawn
+    /// by [Self::encode_draw_pass].
+    pub fn upload_instances(&mut self, queue: &wgpu::Queue, frusta: &[Frustum]) {
+        let visible: Vec<&Instance> = self
+            .instances
+            .iter()
+            .filter(|instance| {
+                frustum::any_intersects_sphere(frusta, instance.bounding_sphere(self.mesh_bounds))
+            })
+            .collect();
+        self.cull_stats = CullStats {
+            drawn: visible.len(),
+            culled: self.instances.len() - visible.len(),
+        };
         queue.write_buffer(
             &self.instance_buffer,
             0,
-            bytemuck::cast_slice(&Self::instances_to_data(&self.instances)),
+            bytemuck::cast_slice(&Self::instances_to_data(visible)),
         );
     }
 
-    fn instances_to_data(poses: &[Instance]) -> Vec<f32> {
-        poses.iter().flat_map(Instance::to_cols_array).collect()
+    /// Uploads instances that are drawn with the same mesh as [Self::instances] to visualise
+    /// debugging information, such as tracked hand joints. They aren't culled, and any beyond
+    /// [Self::MAX_DEBUG_INSTANCES] are dropped.
+    #[cfg_attr(not(feature = "xr"), allow(dead_code))]
+    pub fn upload_debug_instances(&mut self, queue: &wgpu::Queue, instances: &[Instance]) {
+        let instances = &instances[..instances.len().min(Self::MAX_DEBUG_INSTANCES)];
+        self.debug_instance_count = instances.len() as u32;
+        queue.write_buffer(
+            &self.debug_instance_buffer,
+            0,
+            bytemuck::cast_slice(&Self::instances_to_data(instances)),
+        );
+    }
+
+    /// Uploads line segments, each given by its two ends, to be drawn after the instances. Any
+    /// beyond [Self::MAX_LINES] are dropped.
+    #[cfg_attr(not(feature = "xr"), allow(dead_code))]
+    pub fn upload_lines(&mut self, queue: &wgpu::Queue, lines: &[[Vertex; 2]]) {
+        let lines = &lines[..lines.len().min(Self::MAX_LINES)];
+        self.line_count = lines.len() as u32;
+        queue.write_buffer(&self.line_vertex_buffer, 0, bytemuck::cast_slice(lines));
+    }
+
+    /// Casts `ray` against the bounds of [Self::instances], skipping those in `ignored` (such as
+    /// an instance attached to the ray's origin), and returns the nearest hit.
+    #[cfg_attr(not(feature = "xr"), allow(dead_code))]
+    pub fn ray_cast(&self, ray: Ray, ignored: &[usize]) -> Option<RayHit> {
+        self.instances
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !ignored.contains(index))
+            .filter_map(|(index, instance)| {
+                let distance = instance
+                    .bounding_sphere(self.mesh_bounds)
+                    .intersect_ray(ray)?;
+                Some(RayHit {
+                    instance: index,
+                    distance,
+                })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    pub fn cull_stats(&self) -> CullStats {
+        self.cull_stats
+    }
+
+    fn instances_to_data<'a>(poses: impl IntoIterator<Item = &'a Instance>) -> Vec<f32> {
+        poses
+            .into_iter()
+            .flat_map(Instance::to_cols_array)
+            .collect()
     }
 
     pub fn encode_draw_pass(
@@ -144,7 +419,6 @@ impl MainState {
         encoder: &mut wgpu::CommandEncoder,
         rt_view: &wgpu::TextureView,
         depth_view: &wgpu::TextureView,
-        vertex_buffer: &wgpu::Buffer,
         camera_bind_group: &wgpu::BindGroup,
     ) {
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -160,16 +434,27 @@ impl MainState {
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: depth_view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    load: wgpu::LoadOp::Clear(self.depth_mode.clear_value()),
                     store: true,
                 }),
                 stencil_ops: None,
             }),
         });
         rpass.set_pipeline(&self.pipeline);
-        rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         rpass.set_vertex_buffer(1, self.instance_buffer.slice(..));
         rpass.set_bind_group(0, camera_bind_group, &[]);
-        rpass.draw(0..3, 0..(self.instances.len() as u32));
+        rpass.draw(0..self.vertex_count, 0..(self.cull_stats.drawn as u32));
+
+        if self.debug_instance_count > 0 {
+            rpass.set_vertex_buffer(1, self.debug_instance_buffer.slice(..));
+            rpass.draw(0..self.vertex_count, 0..self.debug_instance_count);
+        }
+
+        if self.line_count > 0 {
+            rpass.set_pipeline(&self.line_pipeline);
+            rpass.set_vertex_buffer(0, self.line_vertex_buffer.slice(..));
+            rpass.draw(0..(self.line_count * 2), 0..1);
+        }
     }
 }
@@ -0,0 +1,212 @@
+//! Caches shader modules by the file and [Defines] they were built from, and render pipelines
+//! by their module and the rest of their [PipelineState], so that each permutation is only
+//! compiled once.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    types::DepthMode,
+    wgsl::{with_validation, Defines, Preprocessor},
+};
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ShaderKey {
+    filename: PathBuf,
+    defines: Defines,
+}
+
+/// The state of a render pipeline that can differ between pipelines built from the same module.
+#[derive(Clone, Copy)]
+pub struct PipelineState<'a> {
+    pub layout: &'a Rc<wgpu::PipelineLayout>,
+    /// The format of the single colour target.
+    pub format: wgpu::TextureFormat,
+    /// How the depth buffer is tested, or `None` if there is no depth buffer.
+    pub depth_mode: Option<DepthMode>,
+    pub multiview: Option<NonZeroU32>,
+    pub topology: wgpu::PrimitiveTopology,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    shader: ShaderKey,
+    /// The address of the pipeline's layout. The cached entry keeps the layout alive, so the
+    /// address can't be reused by another layout while the entry exists.
+    layout: *const wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    depth_mode: Option<DepthMode>,
+    multiview: Option<NonZeroU32>,
+    topology: wgpu::PrimitiveTopology,
+}
+
+/// Values built from preprocessed shader source, which are only reused while the source they
+/// were built from is unchanged.
+struct SourceCache<K, V> {
+    entries: HashMap<K, (String, V)>,
+    hits: u64,
+    misses: u64,
+}
+impl<K, V> Default for SourceCache<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+}
+impl<K: Eq + Hash, V: Clone> SourceCache<K, V> {
+    /// Returns the value cached for `key` if it was built from `source`, or otherwise replaces it
+    /// with the result of `create`. Errors are returned without touching the cached value.
+    fn get_or_try_insert(
+        &mut self,
+        key: K,
+        source: &str,
+        create: impl FnOnce() -> anyhow::Result<V>,
+    ) -> anyhow::Result<V> {
+        if let Some((cached_source, value)) = self.entries.get(&key) {
+            if cached_source == source {
+                self.hits += 1;
+                return Ok(value.clone());
+            }
+        }
+
+        self.misses += 1;
+        let value = create()?;
+        self.entries.insert(key, (source.to_owned(), value.clone()));
+        Ok(value)
+    }
+}
+
+/// Hit and miss counts for a [ShaderCache].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub module_hits: u64,
+    pub module_misses: u64,
+    pub pipeline_hits: u64,
+    pub pipeline_misses: u64,
+}
+impl std::fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "modules: {} hits, {} misses | pipelines: {} hits, {} misses",
+            self.module_hits, self.module_misses, self.pipeline_hits, self.pipeline_misses
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct ShaderCache {
+    modules: SourceCache<ShaderKey, Rc<wgpu::ShaderModule>>,
+    pipelines: SourceCache<PipelineKey, (Rc<wgpu::PipelineLayout>, Rc<wgpu::RenderPipeline>)>,
+}
+impl ShaderCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the pipeline with `state` that uses the shader module for `filename` preprocessed
+    /// with `defines`, calling `create` with the module if it is not cached yet.
+    ///
+    /// `create` must build the pipeline from `state`; anything else it sets must be the same for
+    /// every pipeline using the module. The file is preprocessed on every call, and cached
+    /// modules and pipelines are only reused if they were compiled from the same source, so
+    /// those whose files haven't changed survive a reload.
+    pub fn pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        preprocessor: &Preprocessor,
+        filename: impl AsRef<Path>,
+        defines: &Defines,
+        state: PipelineState,
+        create: impl FnOnce(&wgpu::ShaderModule) -> wgpu::RenderPipeline,
+    ) -> anyhow::Result<Rc<wgpu::RenderPipeline>> {
+        let shader = ShaderKey {
+            filename: filename.as_ref().to_owned(),
+            defines: defines.clone(),
+        };
+        let preprocessed = preprocessor.preprocess(&shader.filename, &shader.defines)?;
+        let source = preprocessed.source.clone();
+        let module = self
+            .modules
+            .get_or_try_insert(shader.clone(), &source, || {
+                Ok(Rc::new(
+                    preprocessed.create_shader_module(device, &shader.filename)?,
+                ))
+            })?;
+
+        let key = PipelineKey {
+            shader,
+            layout: Rc::as_ptr(state.layout),
+            format: state.format,
+            depth_mode: state.depth_mode,
+            multiview: state.multiview,
+            topology: state.topology,
+        };
+        let (_, pipeline) = self.pipelines.get_or_try_insert(key, &source, || {
+            let pipeline = with_validation(device, || create(&module))?;
+            Ok((state.layout.clone(), Rc::new(pipeline)))
+        })?;
+        Ok(pipeline)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            module_hits: self.modules.hits,
+            module_misses: self.modules.misses,
+            pipeline_hits: self.pipelines.hits,
+            pipeline_misses: self.pipelines.misses,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SourceCache;
+
+    fn get(
+        cache: &mut SourceCache<&'static str, u32>,
+        key: &'static str,
+        source: &str,
+        value: u32,
+    ) -> u32 {
+        cache.get_or_try_insert(key, source, || Ok(value)).unwrap()
+    }
+
+    #[test]
+    fn reuses_values_built_from_the_same_source() {
+        let mut cache = SourceCache::default();
+        assert_eq!(get(&mut cache, "main.wgsl", "fn a() {}", 1), 1);
+        assert_eq!(get(&mut cache, "main.wgsl", "fn a() {}", 2), 1);
+        assert_eq!((cache.hits, cache.misses), (1, 1));
+    }
+
+    #[test]
+    fn rebuilds_values_whose_source_changed() {
+        let mut cache = SourceCache::default();
+        get(&mut cache, "main.wgsl", "fn a() {}", 1);
+        assert_eq!(get(&mut cache, "main.wgsl", "fn b() {}", 2), 2);
+        assert_eq!(get(&mut cache, "main.wgsl", "fn b() {}", 3), 2);
+        assert_eq!(get(&mut cache, "line.wgsl", "fn b() {}", 4), 4);
+        assert_eq!((cache.hits, cache.misses), (1, 3));
+    }
+
+    #[test]
+    fn keeps_the_cached_value_when_rebuilding_fails() {
+        let mut cache = SourceCache::default();
+        get(&mut cache, "main.wgsl", "fn a() {}", 1);
+        let result = cache.get_or_try_insert("main.wgsl", "fn a( {}", || {
+            anyhow::bail!("expected a parameter")
+        });
+        assert!(result.is_err());
+        assert_eq!(get(&mut cache, "main.wgsl", "fn a() {}", 2), 1);
+        assert_eq!((cache.hits, cache.misses), (1, 2));
+    }
+}
@@ -0,0 +1,139 @@
+use glam::{vec3, Quat, Vec3};
+use winit::event::{ElementState, MouseScrollDelta, VirtualKeyCode};
+
+use crate::{
+    camera::Camera,
+    fly_camera::FlyCamera,
+    input::{ActionId, ActionValue, HandState},
+    xr::{glam_to_openxr_pose, PostFrameData},
+    xr_math::Fov,
+};
+
+/// Stands in for a headset and a pair of controllers when there is no XR hardware, producing the
+/// same [PostFrameData] as [crate::xr::XrState::post_frame] so that the rest of the XR code path
+/// can be exercised.
+///
+/// The head is moved and turned by a [FlyCamera]. The controllers follow the head; dragging with
+/// the right mouse button moves the selected controller around in front of the head and the
+/// scroll wheel moves it nearer or further. Holding T pulls the selected controller's trigger, Tab
+/// swaps the selected controller and H toggles whether the controllers are tracked.
+pub struct SimulatedHeadset {
+    /// The head's position in stage space.
+    pub position: Vec3,
+    /// The head's orientation in stage space.
+    pub rotation: Quat,
+    /// The vertical field of view of each eye.
+    pub fov_y_rad: f32,
+    /// The positions of the left and right controllers relative to the head.
+    pub hand_offsets: [Vec3; 2],
+    pub hands_tracked: bool,
+
+    selected_hand: usize,
+    dragging: bool,
+    trigger_held: bool,
+}
+impl SimulatedHeadset {
+    /// Metres moved per pixel of mouse movement when dragging a controller.
+    const DRAG_SENSITIVITY: f32 = 0.001;
+    /// Metres moved per line scrolled.
+    const SCROLL_STEP: f32 = 0.05;
+
+    pub fn new() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            fov_y_rad: 90.0f32.to_radians(),
+            // The head looks down +Z with -X to its right.
+            hand_offsets: [vec3(0.2, -0.25, 0.4), vec3(-0.2, -0.25, 0.4)],
+            hands_tracked: true,
+
+            selected_hand: 1,
+            dragging: false,
+            trigger_held: false,
+        }
+    }
+
+    /// Whether mouse movement is currently moving a controller rather than turning the head.
+    pub fn dragging(&self) -> bool {
+        self.dragging
+    }
+
+    pub fn set_dragging(&mut self, dragging: bool) {
+        self.dragging = dragging;
+    }
+
+    pub fn handle_key(&mut self, key: VirtualKeyCode, state: ElementState) {
+        match (key, state) {
+            (VirtualKeyCode::Tab, ElementState::Released) => {
+                self.selected_hand = 1 - self.selected_hand;
+            }
+            (VirtualKeyCode::H, ElementState::Released) => {
+                self.hands_tracked = !self.hands_tracked;
+            }
+            (VirtualKeyCode::T, state) => {
+                self.trigger_held = state == ElementState::Pressed;
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves the selected controller by a raw mouse movement, if dragging.
+    pub fn handle_mouse_motion(&mut self, (dx, dy): (f64, f64)) {
+        if !self.dragging {
+            return;
+        }
+        let offset = &mut self.hand_offsets[self.selected_hand];
+        offset.x -= dx as f32 * Self::DRAG_SENSITIVITY;
+        offset.y -= dy as f32 * Self::DRAG_SENSITIVITY;
+    }
+
+    pub fn handle_scroll(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+        };
+        let offset = &mut self.hand_offsets[self.selected_hand];
+        offset.z = (offset.z + lines * Self::SCROLL_STEP).max(0.0);
+    }
+
+    /// Moves and turns the head with `fly_camera` over `dt` seconds.
+    pub fn update(&mut self, fly_camera: &FlyCamera, dt: f32) {
+        self.position += fly_camera.velocity(Vec3::Y) * dt;
+        self.rotation = fly_camera.rotation();
+    }
+
+    /// Produces the views and controller poses for this frame. The views are separated by the
+    /// camera's IPD and share its aspect ratio.
+    pub fn post_frame(&self, camera: &Camera) -> PostFrameData {
+        let fov = Fov::symmetric(self.fov_y_rad, camera.aspect_ratio).into();
+
+        let views = [camera.ipd / 2.0, -camera.ipd / 2.0]
+            .map(|eye_offset| openxr::View {
+                pose: glam_to_openxr_pose(
+                    self.position + self.rotation * vec3(eye_offset, 0.0, 0.0),
+                    self.rotation,
+                ),
+                fov,
+            })
+            .to_vec();
+
+        let mut hands = self.hand_offsets.map(|offset| {
+            HandState::from_grip(
+                self.hands_tracked
+                    .then(|| (self.position + self.rotation * offset, self.rotation)),
+            )
+        });
+        if self.hands_tracked {
+            hands[self.selected_hand].set(
+                ActionId::Trigger,
+                ActionValue::Float(self.trigger_held as u8 as f32),
+            );
+        }
+
+        PostFrameData {
+            views,
+            hands,
+            hand_joints: Default::default(),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::num::NonZeroU32;
 use wgpu::TextureFormat;
 
-use crate::types::{DEPTH_FORMAT, VIEW_COUNT};
+use crate::types::DEPTH_FORMAT;
 use crate::xr::WGPU_COLOR_FORMAT;
 
 pub struct Texture {
@@ -22,6 +22,7 @@ impl Texture {
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
         texture_format: wgpu::TextureFormat,
+        view_count: u32,
     ) -> Self {
         let view_formats = vec![texture_format];
         let texture = device.create_texture(&wgpu::TextureDescriptor {
@@ -29,7 +30,7 @@ impl Texture {
             size: wgpu::Extent3d {
                 width: config.width,
                 height: config.height,
-                depth_or_array_layers: 2,
+                depth_or_array_layers: view_count,
             },
             mip_level_count: 1,
             sample_count: 1,
@@ -40,21 +41,29 @@ impl Texture {
                 | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &view_formats,
         });
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Always an array, even with a single view, to match the multiview pipelines.
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
         Self {
             _texture: texture,
             view,
         }
     }
 
-    pub fn new_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+    pub fn new_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        view_count: u32,
+    ) -> Self {
         let view_formats = vec![DEPTH_FORMAT];
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Depth Texture"),
             size: wgpu::Extent3d {
                 width: config.width,
                 height: config.height,
-                depth_or_array_layers: 2,
+                depth_or_array_layers: view_count,
             },
             mip_level_count: 1,
             sample_count: 1,
@@ -63,7 +72,11 @@ impl Texture {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &view_formats,
         });
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Always an array, even with a single view, to match the multiview pipelines.
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
         Self {
             _texture: texture,
             view,
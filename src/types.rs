@@ -1,7 +1,54 @@
 use glam::{Vec3, Vec4};
 
 pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
-pub const VIEW_COUNT: u32 = 2;
+/// The number of views rendered when there is no XR view configuration to take it from: one
+/// per eye.
+pub const DESKTOP_VIEW_COUNT: u32 = 2;
+
+/// How view-space depth is mapped to the depth buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum DepthMode {
+    /// Depth goes from 0 at the near plane to 1 at the far plane.
+    Standard,
+    /// Depth goes from 1 at the near plane to 0 at the far plane. Combined with a floating-point
+    /// depth buffer, this keeps precision roughly even across the whole range.
+    ReverseZ,
+    /// Like [DepthMode::ReverseZ], but without a far plane.
+    ReverseZInfinite,
+}
+impl DepthMode {
+    /// The value to clear the depth buffer to, i.e. the depth of the farthest possible point.
+    pub fn clear_value(self) -> f32 {
+        match self {
+            Self::Standard => 1.0,
+            Self::ReverseZ | Self::ReverseZInfinite => 0.0,
+        }
+    }
+
+    /// The comparison that passes for fragments closer to the viewer.
+    pub fn compare_function(self) -> wgpu::CompareFunction {
+        match self {
+            Self::Standard => wgpu::CompareFunction::Less,
+            Self::ReverseZ | Self::ReverseZInfinite => wgpu::CompareFunction::Greater,
+        }
+    }
+
+    /// Returns the `z_axis.z` and `w_axis.z` entries of a right-handed projection matrix
+    /// (with `z_axis.w == -1`) that maps view-space depth to this mode's depth range.
+    pub fn projection_depth_terms(self, z_near: f32, z_far: f32) -> (f32, f32) {
+        match self {
+            Self::Standard => (
+                -z_far / (z_far - z_near),
+                -(z_far * z_near) / (z_far - z_near),
+            ),
+            Self::ReverseZ => (
+                z_near / (z_far - z_near),
+                (z_far * z_near) / (z_far - z_near),
+            ),
+            Self::ReverseZInfinite => (0.0, z_near),
+        }
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -16,4 +63,8 @@ impl Vertex {
             color: color.to_array(),
         }
     }
+
+    pub fn position(&self) -> Vec3 {
+        Vec3::from(self.position)
+    }
 }
@@ -1,79 +1,657 @@
 //! Implements a very simple preprocessor to embed other WGSL files.
+//!
+//! Supports the following directives:
+//!   - `#include file.wgsl` or `#include "file.wgsl"`: pastes in the preprocessed contents of
+//!     `file.wgsl`, looked up relative to the including file first and the root second
+//!   - `#pragma once`: only expand the containing file the first time it is included
+//!   - `#define NAME [value]` / `#undef NAME`: add or remove a define
+//!   - `#ifdef NAME` / `#ifndef NAME` / `#else` / `#endif`: conditionally include lines
+//!
+//! Defines with a value are substituted wherever their name appears as an identifier. Defines can
+//! also be passed in from Rust through [Defines]. The output comes with a [LineMap] that can be
+//! used to point shader errors back at the original files.
 
 use std::{
-    collections::HashMap,
-    path::{Path, PathBuf},
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Component, Path, PathBuf},
 };
 
 use anyhow::Context;
 
-/// Preprocesses the given `current_file` within the `files`, and returns the preprocessed file.
-/// `files` must contain non-preprocessed files.
+/// Preprocesses `current_file` within the non-preprocessed `files`, starting with `defines`.
+/// `filename` is the name of `current_file` in diagnostics and is used to resolve its relative
+/// includes.
 ///
 /// This is _not_ a robust preprocessor. It's the bare minimum to make this example work.
 /// This *will* fall down at the first hurdle.
-pub fn preprocess(files: &HashMap<PathBuf, String>, current_file: &str) -> anyhow::Result<String> {
-    let mut current_file = current_file.to_string();
-    while current_file.contains("#include") {
-        current_file = current_file
-            .lines()
-            .map(|l| match l.strip_prefix("#include ") {
-                Some(filename) => Ok(files
-                    .get(&PathBuf::from(filename))
-                    .context("failed to find file")?
-                    .as_ref()),
-                None => Ok(l),
+fn preprocess_named(
+    files: &HashMap<PathBuf, String>,
+    filename: &Path,
+    current_file: &str,
+    defines: &Defines,
+) -> anyhow::Result<Preprocessed> {
+    let mut expander = Expander {
+        files,
+        defines: defines.clone(),
+        stack: vec![],
+        included_once: HashSet::new(),
+        output: vec![],
+        line_map: LineMap::default(),
+    };
+    expander.expand(filename, current_file)?;
+    Ok(Preprocessed {
+        source: expander.output.join("\n"),
+        line_map: expander.line_map,
+    })
+}
+
+/// The result of preprocessing a file with a [Preprocessor].
+pub struct Preprocessed {
+    /// The flattened source, with all includes expanded.
+    pub source: String,
+    /// Maps the lines of `source` back to the files they came from.
+    pub line_map: LineMap,
+}
+
+impl Preprocessed {
+    /// Parses and validates the source with naga, allowing the capabilities that `features`
+    /// enable on a device. Errors point at the original files.
+    pub fn validate(&self, features: wgpu::Features) -> anyhow::Result<naga::Module> {
+        let module = naga::front::wgsl::parse_str(&self.source).map_err(|error| {
+            anyhow::anyhow!(self
+                .line_map
+                .remap_error(&error.emit_to_string(&self.source)))
+        })?;
+
+        let mut capabilities = naga::valid::Capabilities::empty();
+        capabilities.set(
+            naga::valid::Capabilities::PUSH_CONSTANT,
+            features.contains(wgpu::Features::PUSH_CONSTANTS),
+        );
+        capabilities.set(
+            naga::valid::Capabilities::MULTIVIEW,
+            features.contains(wgpu::Features::MULTIVIEW),
+        );
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), capabilities)
+            .validate(&module)
+            .map_err(|error| {
+                anyhow::anyhow!(self
+                    .line_map
+                    .remap_error(&error.emit_to_string(&self.source)))
+            })?;
+
+        Ok(module)
+    }
+
+    /// Validates the source with naga and creates a shader module labelled `filename` from it.
+    /// Any error is returned with its locations pointing at the original files.
+    pub fn create_shader_module(
+        self,
+        device: &wgpu::Device,
+        filename: &Path,
+    ) -> anyhow::Result<wgpu::ShaderModule> {
+        self.validate(device.features())
+            .with_context(|| format!("failed to validate `{}`", filename.display()))?;
+
+        let line_map = self.line_map;
+        with_validation(device, || {
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: filename.to_str(),
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(self.source)),
+            })
+        })
+        .map_err(|error| {
+            anyhow::anyhow!(
+                "failed to create shader module for `{}`: {}",
+                filename.display(),
+                line_map.remap_error(&error.to_string())
+            )
+        })
+    }
+}
+
+/// A set of preprocessor defines, mapping each name to its (possibly empty) value.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Defines(BTreeMap<String, String>);
+impl Defines {
+    /// Creates an empty set of defines.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a define named `name` that is substituted with `value`.
+    pub fn with(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    /// Adds a define named `name` without a value, for use with `#ifdef` and `#ifndef`.
+    pub fn with_flag(self, name: impl Into<String>) -> Self {
+        self.with(name, "")
+    }
+
+    /// Adds or replaces the define named `name`.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl ToString) {
+        self.0.insert(name.into(), value.to_string());
+    }
+
+    /// Removes the define named `name`, if present.
+    pub fn remove(&mut self, name: &str) {
+        self.0.remove(name);
+    }
+
+    /// Returns whether `name` is defined.
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Replaces every identifier in `line` that names a define with a value by that value.
+    fn substitute<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if self.0.values().all(String::is_empty) {
+            return Cow::Borrowed(line);
+        }
+
+        let is_identifier_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let mut output = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            let (before, from_start) = rest.split_at(start);
+            let len = from_start
+                .find(|c: char| !is_identifier_char(c))
+                .unwrap_or(from_start.len());
+            let (word, after) = from_start.split_at(len);
+
+            // A word directly after a digit is the suffix of a numeric literal like `1u` or `0x1f`.
+            let is_literal = before.ends_with(|c: char| c.is_ascii_digit());
+            output.push_str(before);
+            match self.0.get(word) {
+                Some(value) if !value.is_empty() && !is_literal => output.push_str(value),
+                _ => output.push_str(word),
+            }
+            rest = after;
+        }
+        output.push_str(rest);
+        Cow::Owned(output)
+    }
+}
+
+impl std::fmt::Display for Defines {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, (name, value)) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            if value.is_empty() {
+                write!(f, "{name}")?;
+            } else {
+                write!(f, "{name}={value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Maps each line of preprocessed output back to the file and line it originated from.
+#[derive(Debug, Default, Clone)]
+pub struct LineMap {
+    files: Vec<PathBuf>,
+    /// For each output line, the index into `files` and the 1-based line within that file.
+    lines: Vec<(usize, usize)>,
+}
+impl LineMap {
+    /// Returns the original file and 1-based line for the 1-based `line` of the preprocessed output.
+    pub fn lookup(&self, line: usize) -> Option<(&Path, usize)> {
+        let (file_index, original_line) = *self.lines.get(line.checked_sub(1)?)?;
+        Some((&self.files[file_index], original_line))
+    }
+
+    /// Rewrites every `name:line:col` location in `message` (as emitted by naga and wgpu) to
+    /// point at the original file and line instead of the preprocessed output.
+    pub fn remap_error(&self, message: &str) -> String {
+        message
+            .split_inclusive(char::is_whitespace)
+            .map(|piece| {
+                let token = piece.trim_end();
+                match self.remap_location(token) {
+                    Some(remapped) => remapped + &piece[token.len()..],
+                    None => piece.to_string(),
+                }
             })
-            .collect::<anyhow::Result<Vec<&str>>>()?
-            .join("\n");
+            .collect()
+    }
+
+    fn remap_location(&self, token: &str) -> Option<String> {
+        let mut parts = token.rsplitn(3, ':');
+        let column: usize = parts.next()?.parse().ok()?;
+        let line: usize = parts.next()?.parse().ok()?;
+        let _name = parts.next().filter(|name| !name.is_empty())?;
+
+        let (file, original_line) = self.lookup(line)?;
+        Some(format!("{}:{original_line}:{column}", file.display()))
+    }
+
+    fn push(&mut self, file: &Path, line: usize) {
+        let file_index = match self.files.iter().position(|f| f == file) {
+            Some(index) => index,
+            None => {
+                self.files.push(file.to_owned());
+                self.files.len() - 1
+            }
+        };
+        self.lines.push((file_index, line));
+    }
+}
+
+/// Writes one `output_line file:line` entry per line of preprocessed output.
+impl std::fmt::Display for LineMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, (file_index, original_line)) in self.lines.iter().enumerate() {
+            writeln!(
+                f,
+                "{} {}:{original_line}",
+                index + 1,
+                self.files[*file_index].display()
+            )?;
+        }
+        Ok(())
     }
-    Ok(current_file)
 }
 
-/// A helper for [preprocess] that wraps it with some files to use for state.
+/// A preprocessor directive, parsed from a line starting with `#`.
+enum Directive<'a> {
+    Include(&'a str),
+    PragmaOnce,
+    Define(&'a str, &'a str),
+    Undef(&'a str),
+    IfDef(&'a str),
+    IfNDef(&'a str),
+    Else,
+    EndIf,
+}
+impl<'a> Directive<'a> {
+    /// Parses `line` as a directive. Returns `None` if the line is not a directive.
+    fn parse(line: &'a str) -> Option<Self> {
+        let line = line.trim().strip_prefix('#')?;
+        let (name, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let argument = argument.trim();
+        Some(match name {
+            "include" => Self::Include(
+                argument
+                    .strip_prefix('"')
+                    .and_then(|a| a.strip_suffix('"'))
+                    .unwrap_or(argument),
+            ),
+            "pragma" if argument == "once" => Self::PragmaOnce,
+            "define" => {
+                let (name, value) = argument
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((argument, ""));
+                Self::Define(name, value.trim())
+            }
+            "undef" => Self::Undef(argument),
+            "ifdef" => Self::IfDef(argument),
+            "ifndef" => Self::IfNDef(argument),
+            "else" => Self::Else,
+            "endif" => Self::EndIf,
+            _ => return None,
+        })
+    }
+}
+
+/// An `#ifdef`/`#ifndef` block that is currently open.
+struct Conditional {
+    /// Whether lines in the current branch of this block are kept.
+    active: bool,
+    /// Whether the `#else` of this block has been seen.
+    in_else: bool,
+    /// The 1-based line the block was opened on.
+    line: usize,
+}
+
+/// The state of a single [preprocess_named] run.
+struct Expander<'a> {
+    files: &'a HashMap<PathBuf, String>,
+    defines: Defines,
+    /// The chain of files currently being expanded, used to detect include cycles.
+    stack: Vec<PathBuf>,
+    /// Files marked with `#pragma once` that have already been expanded.
+    included_once: HashSet<PathBuf>,
+    output: Vec<Cow<'a, str>>,
+    line_map: LineMap,
+}
+impl<'a> Expander<'a> {
+    /// Recursively expands the directives in `source` into the output.
+    fn expand(&mut self, filename: &Path, source: &'a str) -> anyhow::Result<()> {
+        self.stack.push(filename.to_owned());
+        let mut conditionals: Vec<Conditional> = vec![];
+        for (line_index, line) in source.lines().enumerate() {
+            let line_number = line_index + 1;
+            let location = || format!("{}:{line_number}", filename.display());
+            let active = conditionals.iter().all(|c| c.active);
+
+            let directive = match Directive::parse(line) {
+                Some(directive) => directive,
+                None => {
+                    if active {
+                        self.output.push(self.defines.substitute(line));
+                        self.line_map.push(filename, line_number);
+                    }
+                    continue;
+                }
+            };
+
+            match directive {
+                Directive::IfDef(name) | Directive::IfNDef(name) => {
+                    let is_ifdef = matches!(directive, Directive::IfDef(_));
+                    conditionals.push(Conditional {
+                        active: self.defines.contains(name) == is_ifdef,
+                        in_else: false,
+                        line: line_number,
+                    });
+                }
+                Directive::Else => {
+                    let conditional = conditionals
+                        .last_mut()
+                        .with_context(|| format!("`#else` without `#ifdef` at {}", location()))?;
+                    if conditional.in_else {
+                        anyhow::bail!("duplicate `#else` at {}", location());
+                    }
+                    conditional.active = !conditional.active;
+                    conditional.in_else = true;
+                }
+                Directive::EndIf => {
+                    conditionals
+                        .pop()
+                        .with_context(|| format!("`#endif` without `#ifdef` at {}", location()))?;
+                }
+                _ if !active => {}
+                Directive::PragmaOnce => {
+                    self.included_once.insert(filename.to_owned());
+                }
+                Directive::Define(name, value) => self.defines.insert(name, value),
+                Directive::Undef(name) => self.defines.remove(name),
+                Directive::Include(included) => {
+                    self.include(filename, included, &location())?;
+                }
+            }
+        }
+        if let Some(conditional) = conditionals.last() {
+            anyhow::bail!(
+                "unterminated `#ifdef` at {}:{}",
+                filename.display(),
+                conditional.line
+            );
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// Expands the file `included`, which was included by `includer` at `location`.
+    fn include(&mut self, includer: &Path, included: &str, location: &str) -> anyhow::Result<()> {
+        let included = &resolve_include(self.files, includer, included).with_context(|| {
+            format!("failed to find file `{included}` included from {location}")
+        })?;
+
+        if self.included_once.contains(included) {
+            return Ok(());
+        }
+
+        if self.stack.iter().any(|p| p == included) {
+            let cycle = self
+                .stack
+                .iter()
+                .map(PathBuf::as_path)
+                .skip_while(|p| *p != included)
+                .chain(std::iter::once(included.as_path()))
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>();
+            anyhow::bail!("include cycle detected: {}", cycle.join(" -> "));
+        }
+
+        let included_source = &self.files[included];
+        self.expand(included, included_source)
+    }
+}
+
+/// Finds the file in `files` that `included` refers to, looking relative to the directory of
+/// `includer` first and then relative to the root of `files`.
+fn resolve_include(
+    files: &HashMap<PathBuf, String>,
+    includer: &Path,
+    included: &str,
+) -> Option<PathBuf> {
+    let relative_to_includer = includer.parent().unwrap_or(Path::new("")).join(included);
+    [relative_to_includer, PathBuf::from(included)]
+        .into_iter()
+        .map(|path| normalize_path(&path))
+        .find(|path| files.contains_key(path))
+}
+
+/// Lexically resolves the `.` and `..` components of `path`.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The contents of `shaders/`, embedded at compile time so that the binary can be run from any
+/// working directory. Shaders added to `shaders/` need to be added here as well.
+const EMBEDDED_SHADERS: &[(&str, &str)] = &[
+    ("blit.wgsl", include_str!("../shaders/blit.wgsl")),
+    (
+        "blit_common.wgsl",
+        include_str!("../shaders/blit_common.wgsl"),
+    ),
+    ("camera.wgsl", include_str!("../shaders/camera.wgsl")),
+    ("line.wgsl", include_str!("../shaders/line.wgsl")),
+    ("main.wgsl", include_str!("../shaders/main.wgsl")),
+];
+
+/// A helper for [preprocess_named] that wraps it with some files to use for state.
+#[derive(Clone)]
 pub struct Preprocessor {
+    /// Whether [EMBEDDED_SHADERS] are used as the base set of files.
+    embedded: bool,
+    /// The directory files are read from, overriding any embedded files with the same name.
+    directory: Option<PathBuf>,
     files: HashMap<PathBuf, String>,
 }
 impl Preprocessor {
     /// Create a [Preprocessor] from the given `path`.
     pub fn from_directory(path: &Path) -> std::io::Result<Self> {
-        Ok(Self {
-            files: std::fs::read_dir(path)?
-                .filter_map(Result::ok)
-                .map(|de| de.path())
-                .filter(|p| p.extension().unwrap_or_default() == "wgsl")
-                .map(|p| {
-                    Ok((
-                        PathBuf::from(
-                            p.file_name().ok_or_else(|| {
-                                std::io::Error::from(std::io::ErrorKind::NotFound)
-                            })?,
-                        ),
-                        std::fs::read_to_string(&p)?,
-                    ))
-                })
-                .collect::<std::io::Result<_>>()?,
-        })
+        Self::load(false, Some(path.to_owned()))
+    }
+
+    /// Create a [Preprocessor] from the shaders embedded in the binary. If `override_directory`
+    /// is given, the files in it take precedence over the embedded files with the same name.
+    pub fn embedded(override_directory: Option<&Path>) -> std::io::Result<Self> {
+        Self::load(true, override_directory.map(Path::to_owned))
+    }
+
+    fn load(embedded: bool, directory: Option<PathBuf>) -> std::io::Result<Self> {
+        let mut preprocessor = Self {
+            embedded,
+            directory,
+            files: HashMap::new(),
+        };
+        preprocessor.files = preprocessor.read_files()?;
+        Ok(preprocessor)
+    }
+
+    /// Re-reads the files from the directory this preprocessor was created with, and returns
+    /// whether any of them changed. Always returns `false` if there is no directory.
+    pub fn reload(&mut self) -> std::io::Result<bool> {
+        if self.directory.is_none() {
+            return Ok(false);
+        }
+
+        let files = self.read_files()?;
+        let changed = files != self.files;
+        self.files = files;
+        Ok(changed)
     }
 
-    /// Runs [crate::preprocess] on the given `filename`, assuming that it is within the files that
-    /// initialized this preprocessor.
-    pub fn preprocess(&self, filename: impl AsRef<Path>) -> anyhow::Result<String> {
-        preprocess(
+    fn read_files(&self) -> std::io::Result<HashMap<PathBuf, String>> {
+        let mut files: HashMap<_, _> = if self.embedded {
+            EMBEDDED_SHADERS
+                .iter()
+                .map(|(name, source)| (PathBuf::from(name), source.to_string()))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        if let Some(directory) = &self.directory {
+            files.extend(read_directory(directory)?);
+        }
+        Ok(files)
+    }
+
+    /// Runs [preprocess_named] on the given `filename` with the given `defines`, assuming that it
+    /// is within the files that initialized this preprocessor.
+    pub fn preprocess(
+        &self,
+        filename: impl AsRef<Path>,
+        defines: &Defines,
+    ) -> anyhow::Result<Preprocessed> {
+        let filename = filename.as_ref();
+        preprocess_named(
             &self.files,
+            filename,
             self.files
-                .get(filename.as_ref())
-                .context("file not present")?
+                .get(filename)
+                .with_context(|| format!("file `{}` not present", filename.display()))?
                 .as_str(),
+            defines,
         )
     }
+
+    /// Returns the files that are not included by any other file, sorted by path. These are the
+    /// files that shaders are created from.
+    pub fn entry_points(&self) -> Vec<&Path> {
+        let included: HashSet<PathBuf> = self
+            .files
+            .iter()
+            .flat_map(|(filename, source)| {
+                source
+                    .lines()
+                    .filter_map(move |line| match Directive::parse(line) {
+                        Some(Directive::Include(included)) => {
+                            resolve_include(&self.files, filename, included)
+                        }
+                        _ => None,
+                    })
+            })
+            .collect();
+
+        let mut entry_points: Vec<_> = self
+            .files
+            .keys()
+            .filter(|filename| !included.contains(*filename))
+            .map(PathBuf::as_path)
+            .collect();
+        entry_points.sort();
+        entry_points
+    }
+
+    /// Preprocesses every [entry point](Self::entry_points) with each of `define_sets`, and
+    /// validates the result with naga using the capabilities that `features` enable. Does not
+    /// need a GPU. All of the errors are reported together.
+    pub fn validate_all(
+        &self,
+        features: wgpu::Features,
+        define_sets: &[Defines],
+    ) -> anyhow::Result<()> {
+        let errors: Vec<_> = self
+            .entry_points()
+            .into_iter()
+            .flat_map(|filename| define_sets.iter().map(move |defines| (filename, defines)))
+            .filter_map(|(filename, defines)| {
+                self.preprocess(filename, defines)
+                    .and_then(|preprocessed| preprocessed.validate(features))
+                    .err()
+                    .map(|error| {
+                        format!(
+                            "`{}` with defines [{defines}]: {error:#}",
+                            filename.display()
+                        )
+                    })
+            })
+            .collect();
+
+        if !errors.is_empty() {
+            anyhow::bail!(
+                "{} shader validation error(s):\n{}",
+                errors.len(),
+                errors.join("\n")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Reads all of the WGSL files in `path` and its subdirectories, keyed by their path relative to
+/// `path`.
+fn read_directory(path: &Path) -> std::io::Result<HashMap<PathBuf, String>> {
+    let mut files = HashMap::new();
+    let mut directories = vec![path.to_owned()];
+    while let Some(directory) = directories.pop() {
+        for entry in std::fs::read_dir(&directory)? {
+            let entry_path = entry?.path();
+            if entry_path.is_dir() {
+                directories.push(entry_path);
+            } else if entry_path.extension().unwrap_or_default() == "wgsl" {
+                let relative_path = entry_path
+                    .strip_prefix(path)
+                    .map_err(|_| std::io::Error::from(std::io::ErrorKind::NotFound))?
+                    .to_owned();
+                files.insert(relative_path, std::fs::read_to_string(&entry_path)?);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Runs `create` within a validation error scope on `device`, returning the error it raised
+/// instead of letting it reach the device's uncaptured error handler.
+pub fn with_validation<T>(device: &wgpu::Device, create: impl FnOnce() -> T) -> anyhow::Result<T> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = create();
+    match futures::executor::block_on(device.pop_error_scope()) {
+        Some(error) => anyhow::bail!("{error}"),
+        None => Ok(value),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    use super::{preprocess_named, Defines, Preprocessor};
 
-    use super::preprocess;
+    /// The name used for `current_file` in diagnostics when it doesn't come from `files`.
+    const ROOT_FILE_NAME: &str = "<input>";
+
+    fn preprocess(files: &HashMap<PathBuf, String>, current_file: &str) -> anyhow::Result<String> {
+        Ok(preprocess_named(
+            files,
+            Path::new(ROOT_FILE_NAME),
+            current_file,
+            &Defines::default(),
+        )?
+        .source)
+    }
 
     #[test]
     fn preprocess_can_include() {
@@ -95,4 +673,233 @@ mod tests {
 
         assert_eq!(preprocess(&files, main_file).unwrap(), expected_output);
     }
+
+    #[test]
+    fn preprocess_reports_include_cycle() {
+        let main_file = "#include a.wgsl";
+        let files = [
+            (PathBuf::from("a.wgsl"), "// a\n#include b.wgsl".to_string()),
+            (PathBuf::from("b.wgsl"), "// b\n#include a.wgsl".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        let error = preprocess(&files, main_file).unwrap_err().to_string();
+        assert_eq!(error, "include cycle detected: a.wgsl -> b.wgsl -> a.wgsl");
+    }
+
+    #[test]
+    fn preprocess_reports_missing_file() {
+        let main_file = "// main\n#include blah.wgsl";
+        let files = [(
+            PathBuf::from("blah.wgsl"),
+            "// blah\n\n#include missing.wgsl".to_string(),
+        )]
+        .into_iter()
+        .collect();
+
+        let error = preprocess(&files, main_file).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "failed to find file `missing.wgsl` included from blah.wgsl:3"
+        );
+    }
+
+    #[test]
+    fn preprocess_includes_pragma_once_files_once() {
+        let main_file = "#include a.wgsl\n#include b.wgsl\n// main";
+        let files = [
+            (
+                PathBuf::from("common.wgsl"),
+                "#pragma once\n// common".to_string(),
+            ),
+            (
+                PathBuf::from("a.wgsl"),
+                "#include common.wgsl\n// a".to_string(),
+            ),
+            (
+                PathBuf::from("b.wgsl"),
+                "#include common.wgsl\n// b".to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let expected_output = r#"// common
+// a
+// b
+// main"#;
+
+        assert_eq!(preprocess(&files, main_file).unwrap(), expected_output);
+    }
+
+    #[test]
+    fn line_map_points_at_original_files() {
+        let main_file = "// main\n#include blah.wgsl\nfn main() {}";
+        let files = [
+            (PathBuf::from("foo.wgsl"), "// foo".to_string()),
+            (
+                PathBuf::from("blah.wgsl"),
+                "// blah\n#include foo.wgsl\nlet x = y;".to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let preprocessed =
+            preprocess_named(&files, Path::new("main.wgsl"), main_file, &Defines::new()).unwrap();
+        let line_map = &preprocessed.line_map;
+        assert_eq!(line_map.lookup(1), Some((Path::new("main.wgsl"), 1)));
+        assert_eq!(line_map.lookup(2), Some((Path::new("blah.wgsl"), 1)));
+        assert_eq!(line_map.lookup(3), Some((Path::new("foo.wgsl"), 1)));
+        assert_eq!(line_map.lookup(4), Some((Path::new("blah.wgsl"), 3)));
+        assert_eq!(line_map.lookup(5), Some((Path::new("main.wgsl"), 3)));
+        assert_eq!(line_map.lookup(6), None);
+
+        assert_eq!(
+            line_map
+                .remap_error("error: no definition in scope for identifier: 'y'\n  ┌─ wgsl:4:9\n"),
+            "error: no definition in scope for identifier: 'y'\n  ┌─ blah.wgsl:3:9\n"
+        );
+    }
+
+    #[test]
+    fn preprocess_evaluates_conditionals() {
+        let main_file = r#"#define LOCAL
+#ifdef MULTIVIEW
+// multiview
+#ifndef LOCAL
+// not local
+#else
+// local
+#endif
+#else
+// single view
+#endif
+// done"#;
+        let files = Default::default();
+
+        let expected_output = r#"// multiview
+// local
+// done"#;
+        let defines = Defines::new().with_flag("MULTIVIEW");
+        let preprocessed =
+            preprocess_named(&files, Path::new("main.wgsl"), main_file, &defines).unwrap();
+        assert_eq!(preprocessed.source, expected_output);
+        assert_eq!(
+            preprocessed.line_map.lookup(2),
+            Some((Path::new("main.wgsl"), 7))
+        );
+
+        assert_eq!(
+            preprocess(&files, main_file).unwrap(),
+            "// single view\n// done"
+        );
+    }
+
+    #[test]
+    fn preprocess_substitutes_defines() {
+        let main_file = r#"#define SCALE 2.0
+var<uniform> matrices: array<mat4x4<f32>, VIEW_COUNT>;
+let scale = SCALE * 1u + VIEW_COUNT_2 + 0xVIEW_COUNT;"#;
+        let files = Default::default();
+
+        let expected_output = r#"var<uniform> matrices: array<mat4x4<f32>, 2>;
+let scale = 2.0 * 1u + VIEW_COUNT_2 + 0xVIEW_COUNT;"#;
+        let defines = Defines::new().with("VIEW_COUNT", 2);
+        let preprocessed =
+            preprocess_named(&files, Path::new("main.wgsl"), main_file, &defines).unwrap();
+        assert_eq!(preprocessed.source, expected_output);
+    }
+
+    #[test]
+    fn preprocess_reports_unbalanced_conditionals() {
+        let files = Default::default();
+
+        let error = preprocess(&files, "#ifdef FOO\n// foo")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "unterminated `#ifdef` at <input>:1");
+
+        let error = preprocess(&files, "// foo\n#endif")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "`#endif` without `#ifdef` at <input>:2");
+    }
+
+    #[test]
+    fn embedded_shaders_match_directory() {
+        let shader_directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders");
+        let from_directory = Preprocessor::from_directory(&shader_directory).unwrap();
+        let embedded = Preprocessor::embedded(None).unwrap();
+        assert_eq!(embedded.files, from_directory.files);
+    }
+
+    #[test]
+    fn preprocess_resolves_relative_includes() {
+        let main_file = "#include \"lighting/pbr.wgsl\"\n#include common.wgsl";
+        let files = [
+            (PathBuf::from("common.wgsl"), "// common".to_string()),
+            (
+                PathBuf::from("lighting/common.wgsl"),
+                "// lighting common".to_string(),
+            ),
+            (
+                PathBuf::from("lighting/pbr.wgsl"),
+                "#include common.wgsl\n#include ../common.wgsl\n#include \"lighting/brdf.wgsl\""
+                    .to_string(),
+            ),
+            (PathBuf::from("lighting/brdf.wgsl"), "// brdf".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        let expected_output = r#"// lighting common
+// common
+// brdf
+// common"#;
+
+        assert_eq!(preprocess(&files, main_file).unwrap(), expected_output);
+    }
+
+    #[test]
+    fn from_directory_reads_subdirectories() {
+        let root = std::env::temp_dir().join(format!("wgsl-preprocessor-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("lighting")).unwrap();
+        std::fs::write(root.join("main.wgsl"), "#include lighting/pbr.wgsl").unwrap();
+        std::fs::write(root.join("lighting/pbr.wgsl"), "// pbr").unwrap();
+        std::fs::write(root.join("lighting/notes.txt"), "not a shader").unwrap();
+
+        let preprocessor = Preprocessor::from_directory(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+        let preprocessor = preprocessor.unwrap();
+
+        assert_eq!(preprocessor.files.len(), 2);
+        assert_eq!(
+            preprocessor
+                .preprocess("main.wgsl", &Defines::new())
+                .unwrap()
+                .source,
+            "// pbr"
+        );
+    }
+
+    #[test]
+    fn embedded_shaders_validate() {
+        let preprocessor = Preprocessor::embedded(None).unwrap();
+        assert_eq!(
+            preprocessor.entry_points(),
+            [
+                Path::new("blit.wgsl"),
+                Path::new("line.wgsl"),
+                Path::new("main.wgsl")
+            ]
+        );
+
+        let features = wgpu::Features::MULTIVIEW | wgpu::Features::PUSH_CONSTANTS;
+        let defines = Defines::new().with("VIEW_COUNT", 2);
+        preprocessor
+            .validate_all(features, &[defines.clone(), defines.with_flag("MULTIVIEW")])
+            .unwrap();
+    }
 }
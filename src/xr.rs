@@ -5,46 +5,191 @@ use std::{
 
 use anyhow::Context;
 use ash::vk::{self, Handle};
-use glam::{Quat, Vec3};
-use openxr::{self as xr, ViewConfigurationView};
+use glam::{vec2, vec3, Quat, Vec3};
+use openxr as xr;
 use wgpu::TextureFormat;
 
-use crate::{texture::Texture, types::VIEW_COUNT, WgpuState};
+use crate::{
+    camera_path::ViewPose,
+    input::{
+        ActionDesc, ActionId, ActionKind, ActionValue, Hand, HandJoint, HandJoints, HandState,
+        Vibration, ACTIONS, INTERACTION_PROFILES,
+    },
+    texture::Texture,
+    xr_math::{self, Fov},
+    WgpuState,
+};
 
 pub const WGPU_COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
 pub const VK_COLOR_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
 
-const VIEW_TYPE: xr::ViewConfigurationType = xr::ViewConfigurationType::PRIMARY_STEREO;
+/// The view configurations that can be rendered. The first of these in the runtime's list, which
+/// is in the runtime's order of preference, is used.
+const SUPPORTED_VIEW_TYPES: [xr::ViewConfigurationType; 3] = [
+    xr::ViewConfigurationType::PRIMARY_QUAD_VARJO,
+    xr::ViewConfigurationType::PRIMARY_STEREO,
+    xr::ViewConfigurationType::PRIMARY_MONO,
+];
 
 #[derive(Default)]
 pub struct PostFrameData {
     pub views: Vec<openxr::View>,
-    pub left_hand: Option<(Vec3, Quat)>,
-    pub right_hand: Option<(Vec3, Quat)>,
+    /// The state of each hand's actions, indexed by [Hand].
+    pub hands: [HandState; 2],
+    /// The joints of each hand, indexed by [Hand], if it is being tracked directly.
+    pub hand_joints: [Option<HandJoints>; 2],
+}
+impl PostFrameData {
+    pub fn hand(&self, hand: Hand) -> &HandState {
+        &self.hands[hand as usize]
+    }
 }
 
+/// An OpenXR action created from an [ActionDesc], with a subaction path for each hand.
+enum XrAction {
+    Boolean(xr::Action<bool>),
+    Float(xr::Action<f32>),
+    Vector2(xr::Action<xr::Vector2f>),
+    /// A pose action and a space per hand to locate it in, which are created once the action set
+    /// has been attached to the session.
+    Pose(xr::Action<xr::Posef>, Vec<xr::Space>),
+    Haptic(xr::Action<xr::Haptic>),
+}
+impl XrAction {
+    fn create(
+        action_set: &xr::ActionSet,
+        desc: &ActionDesc,
+        hand_paths: &[xr::Path],
+    ) -> xr::Result<Self> {
+        let (name, localized_name) = (desc.name, desc.localized_name);
+        Ok(match desc.kind {
+            ActionKind::Boolean => {
+                Self::Boolean(action_set.create_action(name, localized_name, hand_paths)?)
+            }
+            ActionKind::Float => {
+                Self::Float(action_set.create_action(name, localized_name, hand_paths)?)
+            }
+            ActionKind::Vector2 => {
+                Self::Vector2(action_set.create_action(name, localized_name, hand_paths)?)
+            }
+            ActionKind::Pose => Self::Pose(
+                action_set.create_action(name, localized_name, hand_paths)?,
+                vec![],
+            ),
+            ActionKind::Haptic => {
+                Self::Haptic(action_set.create_action(name, localized_name, hand_paths)?)
+            }
+        })
+    }
+
+    fn binding(&self, path: xr::Path) -> xr::Binding<'_> {
+        match self {
+            Self::Boolean(action) => xr::Binding::new(action, path),
+            Self::Float(action) => xr::Binding::new(action, path),
+            Self::Vector2(action) => xr::Binding::new(action, path),
+            Self::Pose(action, _) => xr::Binding::new(action, path),
+            Self::Haptic(action) => xr::Binding::new(action, path),
+        }
+    }
+}
+
+/// Converts an OpenXR pose to the renderer's world space with [xr_math::pose_to_glam].
 pub fn openxr_pose_to_glam(pose: &openxr::Posef) -> (Vec3, Quat) {
-    // with enough sign errors anything is possible
-    let rotation = {
-        let o = pose.orientation;
-        Quat::from_rotation_x(180.0f32.to_radians()) * glam::quat(o.w, o.z, o.y, o.x)
-    };
-    let translation = glam::vec3(-pose.position.x, pose.position.y, -pose.position.z);
-    (translation, rotation)
+    let (p, o) = (pose.position, pose.orientation);
+    xr_math::pose_to_glam(vec3(p.x, p.y, p.z), Quat::from_xyzw(o.x, o.y, o.z, o.w))
+}
+
+/// The inverse of [openxr_pose_to_glam].
+pub fn glam_to_openxr_pose(translation: Vec3, rotation: Quat) -> openxr::Posef {
+    let (position, orientation) = xr_math::glam_to_pose(translation, rotation);
+    openxr_pose(position, orientation)
+}
+
+fn openxr_pose(p: Vec3, o: Quat) -> openxr::Posef {
+    openxr::Posef {
+        orientation: openxr::Quaternionf {
+            x: o.x,
+            y: o.y,
+            z: o.z,
+            w: o.w,
+        },
+        position: openxr::Vector3f {
+            x: p.x,
+            y: p.y,
+            z: p.z,
+        },
+    }
+}
+
+impl From<openxr::Fovf> for Fov {
+    fn from(fov: openxr::Fovf) -> Self {
+        Self {
+            angle_left: fov.angle_left,
+            angle_right: fov.angle_right,
+            angle_up: fov.angle_up,
+            angle_down: fov.angle_down,
+        }
+    }
+}
+impl From<Fov> for openxr::Fovf {
+    fn from(fov: Fov) -> Self {
+        Self {
+            angle_left: fov.angle_left,
+            angle_right: fov.angle_right,
+            angle_up: fov.angle_up,
+            angle_down: fov.angle_down,
+        }
+    }
+}
+
+impl From<xr::HandJointLocation> for HandJoint {
+    fn from(location: xr::HandJointLocation) -> Self {
+        let (translation, rotation) = openxr_pose_to_glam(&location.pose);
+        let flags = location.location_flags;
+        Self {
+            translation,
+            rotation,
+            radius: location.radius,
+            position_valid: flags.contains(xr::SpaceLocationFlags::POSITION_VALID),
+            orientation_valid: flags.contains(xr::SpaceLocationFlags::ORIENTATION_VALID),
+        }
+    }
+}
+
+impl From<&openxr::View> for ViewPose {
+    fn from(view: &openxr::View) -> Self {
+        let (p, o) = (view.pose.position, view.pose.orientation);
+        Self {
+            position: vec3(p.x, p.y, p.z),
+            orientation: Quat::from_xyzw(o.x, o.y, o.z, o.w),
+            fov: view.fov.into(),
+        }
+    }
+}
+impl From<&ViewPose> for openxr::View {
+    fn from(view: &ViewPose) -> Self {
+        Self {
+            pose: openxr_pose(view.position, view.orientation),
+            fov: view.fov.into(),
+        }
+    }
 }
 
 pub struct XrState {
     xr_instance: xr::Instance,
     environment_blend_mode: xr::EnvironmentBlendMode,
+    view_type: xr::ViewConfigurationType,
     session: xr::Session<xr::Vulkan>,
     session_running: bool,
     frame_wait: xr::FrameWaiter,
     frame_stream: xr::FrameStream<xr::Vulkan>,
     action_set: xr::ActionSet,
-    right_action: xr::Action<xr::Posef>,
-    left_action: xr::Action<xr::Posef>,
-    right_space: xr::Space,
-    left_space: xr::Space,
+    /// The actions of [ACTIONS], in the same order.
+    actions: Vec<XrAction>,
+    /// The path of each [Hand].
+    hand_paths: [xr::Path; 2],
+    /// A tracker for each [Hand], if the runtime supports `XR_EXT_hand_tracking`.
+    hand_trackers: Option<[xr::HandTracker; 2]>,
     stage: xr::Space,
     event_storage: xr::EventDataBuffer,
     views: Vec<openxr::ViewConfigurationView>,
@@ -64,6 +209,10 @@ impl XrState {
 
         let mut enabled_extensions = xr::ExtensionSet::default();
         enabled_extensions.khr_vulkan_enable2 = true;
+        enabled_extensions.varjo_quad_views = available_extensions.varjo_quad_views;
+        enabled_extensions.ext_hp_mixed_reality_controller =
+            available_extensions.ext_hp_mixed_reality_controller;
+        enabled_extensions.ext_hand_tracking = available_extensions.ext_hand_tracking;
         #[cfg(target_os = "android")]
         {
             enabled_extensions.khr_android_create_instance = true;
@@ -94,8 +243,21 @@ impl XrState {
             }
         );
 
+        // The extension can be available without the system having any way to track hands.
+        let hand_tracking_supported = available_extensions.ext_hand_tracking
+            && xr_instance.supports_hand_tracking(xr_system_id)?;
+        log::info!("hand tracking supported: {hand_tracking_supported}");
+
+        // The runtime lists its view configurations in its order of preference.
+        let view_type = xr_instance
+            .enumerate_view_configurations(xr_system_id)?
+            .into_iter()
+            .find(|view_type| SUPPORTED_VIEW_TYPES.contains(view_type))
+            .context("the OpenXR runtime doesn't support any usable view configuration")?;
+        log::info!("using view configuration {view_type:?}");
+
         let environment_blend_mode =
-            xr_instance.enumerate_environment_blend_modes(xr_system_id, VIEW_TYPE)?[0];
+            xr_instance.enumerate_environment_blend_modes(xr_system_id, view_type)?[0];
         let vk_target_version = vk::make_api_version(0, 1, 1, 0);
         let vk_target_version_xr = xr::Version::new(1, 1, 0);
         let reqs = xr_instance.graphics_requirements::<xr::Vulkan>(xr_system_id)?;
@@ -268,37 +430,61 @@ impl XrState {
                 },
             )
         }?;
-        let action_set = xr_instance.create_action_set("input", "input pose information", 0)?;
-        let right_action =
-            action_set.create_action::<xr::Posef>("right_hand", "Right Hand Controller", &[])?;
-        let left_action =
-            action_set.create_action::<xr::Posef>("left_hand", "Left Hand Controller", &[])?;
-        xr_instance.suggest_interaction_profile_bindings(
-            xr_instance.string_to_path("/interaction_profiles/khr/simple_controller")?,
-            &[
-                xr::Binding::new(
-                    &right_action,
-                    xr_instance.string_to_path("/user/hand/right/input/grip/pose")?,
-                ),
-                xr::Binding::new(
-                    &left_action,
-                    xr_instance.string_to_path("/user/hand/left/input/grip/pose")?,
-                ),
-            ],
-        )?;
+        let action_set = xr_instance.create_action_set("input", "controller input", 0)?;
+        let hand_paths = [
+            xr_instance.string_to_path(Hand::Left.path())?,
+            xr_instance.string_to_path(Hand::Right.path())?,
+        ];
+        let mut actions = ACTIONS
+            .iter()
+            .map(|desc| XrAction::create(&action_set, desc, &hand_paths))
+            .collect::<xr::Result<Vec<_>>>()?;
+        for profile in INTERACTION_PROFILES {
+            let bindings = profile
+                .bindings
+                .iter()
+                .flat_map(|binding| binding.paths().map(move |path| (binding.action, path)))
+                .map(|(action, path)| {
+                    Ok(actions[action as usize].binding(xr_instance.string_to_path(&path)?))
+                })
+                .collect::<xr::Result<Vec<_>>>()?;
+            // Runtimes reject profiles they don't know, such as those from extensions that
+            // aren't available, but the other profiles can still be used.
+            if let Err(e) = xr_instance.suggest_interaction_profile_bindings(
+                xr_instance.string_to_path(profile.path)?,
+                &bindings,
+            ) {
+                log::warn!("failed to suggest bindings for {}: {e}", profile.path);
+            }
+        }
         session.attach_action_sets(&[&action_set])?;
-        let right_space =
-            right_action.create_space(session.clone(), xr::Path::NULL, xr::Posef::IDENTITY)?;
-        let left_space =
-            left_action.create_space(session.clone(), xr::Path::NULL, xr::Posef::IDENTITY)?;
+        for action in &mut actions {
+            if let XrAction::Pose(action, spaces) = action {
+                *spaces = hand_paths
+                    .iter()
+                    .map(|&path| action.create_space(session.clone(), path, xr::Posef::IDENTITY))
+                    .collect::<xr::Result<_>>()?;
+            }
+        }
         let stage =
             session.create_reference_space(xr::ReferenceSpaceType::STAGE, xr::Posef::IDENTITY)?;
 
+        let hand_trackers = if hand_tracking_supported {
+            Some([
+                session.create_hand_tracker(xr::Hand::LEFT)?,
+                session.create_hand_tracker(xr::Hand::RIGHT)?,
+            ])
+        } else {
+            None
+        };
+
+        // Every view is rendered to a layer of the same texture array, so views with different
+        // recommended resolutions (such as the inner views of a quad-view display) are all
+        // rendered at the largest.
         let views = xr_instance
-            .enumerate_view_configuration_views(xr_system_id, VIEW_TYPE)
+            .enumerate_view_configuration_views(xr_system_id, view_type)
             .unwrap();
-        assert_eq!(views.len(), VIEW_COUNT as usize);
-        assert_eq!(views[0], views[1]);
+        log::info!("{} views: {views:#?}", views.len());
 
         Ok((
             WgpuState {
@@ -310,15 +496,15 @@ impl XrState {
             XrState {
                 xr_instance,
                 environment_blend_mode,
+                view_type,
                 session,
                 session_running: false,
                 frame_wait,
                 frame_stream,
                 action_set,
-                right_action,
-                left_action,
-                right_space,
-                left_space,
+                actions,
+                hand_paths,
+                hand_trackers,
                 stage,
                 event_storage: xr::EventDataBuffer::new(),
                 views,
@@ -337,7 +523,7 @@ impl XrState {
                     log::info!("entered state {:?}", e.state());
                     match e.state() {
                         xr::SessionState::READY => {
-                            self.session.begin(VIEW_TYPE)?;
+                            self.session.begin(self.view_type)?;
                             self.session_running = true;
                         }
                         xr::SessionState::STOPPING => {
@@ -356,6 +542,19 @@ impl XrState {
                 EventsLost(e) => {
                     log::warn!("lost {} events", e.lost_event_count());
                 }
+                InteractionProfileChanged(_) => {
+                    // The runtime has picked a profile for the controllers in use, which also
+                    // decides which actions will be active.
+                    for (hand, &hand_path) in Hand::ALL.iter().zip(&self.hand_paths) {
+                        let profile = self.session.current_interaction_profile(hand_path)?;
+                        if profile == xr::Path::NULL {
+                            log::info!("{hand:?} hand has no interaction profile");
+                        } else {
+                            let profile = self.xr_instance.path_to_string(profile)?;
+                            log::info!("{hand:?} hand is using interaction profile {profile}");
+                        }
+                    }
+                }
                 _ => {}
             }
         }
@@ -392,18 +591,30 @@ impl XrState {
             return Ok(PostFrameData::default());
         }
 
+        self.session.sync_actions(&[(&self.action_set).into()])?;
+        let hands = [
+            self.hand_state(Hand::Left, xr_frame_state.predicted_display_time)?,
+            self.hand_state(Hand::Right, xr_frame_state.predicted_display_time)?,
+        ];
+        let hand_joints = match &self.hand_trackers {
+            Some([left, right]) => [
+                self.locate_hand_joints(left, xr_frame_state.predicted_display_time)?,
+                self.locate_hand_joints(right, xr_frame_state.predicted_display_time)?,
+            ],
+            None => Default::default(),
+        };
+
+        let (width, height) = self.resolution();
+        let view_count = self.view_count();
         let swapchain = self.swapchain.get_or_insert_with(|| {
             // Now we need to find all the viewpoints we need to take care of! This is a
-            // property of the view configuration type; in this example we use PRIMARY_STEREO,
-            // so we should have 2 viewpoints.
+            // property of the view configuration type; PRIMARY_STEREO has 2 viewpoints, while
+            // PRIMARY_MONO has 1 and PRIMARY_QUAD_VARJO has 4.
 
             // Create a swapchain for the viewpoints! A swapchain is a set of texture buffers
             // used for displaying to screen, typically this is a backbuffer and a front buffer,
             // one for rendering data to, and one for displaying on-screen.
-            let resolution = vk::Extent2D {
-                width: self.views[0].recommended_image_rect_width,
-                height: self.views[0].recommended_image_rect_height,
-            };
+            let resolution = vk::Extent2D { width, height };
             let handle = self
                 .session
                 .create_swapchain(&xr::SwapchainCreateInfo {
@@ -418,7 +629,7 @@ impl XrState {
                     width: resolution.width,
                     height: resolution.height,
                     face_count: 1,
-                    array_size: VIEW_COUNT,
+                    array_size: view_count,
                     mip_count: 1,
                 })
                 .unwrap();
@@ -442,7 +653,7 @@ impl XrState {
                                     size: wgpu::Extent3d {
                                         width: resolution.width,
                                         height: resolution.height,
-                                        depth_or_array_layers: 2,
+                                        depth_or_array_layers: view_count,
                                     },
                                     mip_level_count: 1,
                                     sample_count: 1,
@@ -464,7 +675,7 @@ impl XrState {
                                     size: wgpu::Extent3d {
                                         width: resolution.width,
                                         height: resolution.height,
-                                        depth_or_array_layers: 2,
+                                        depth_or_array_layers: view_count,
                                     },
                                     mip_level_count: 1,
                                     sample_count: 1,
@@ -478,7 +689,7 @@ impl XrState {
                         };
                         let view = texture.create_view(&wgpu::TextureViewDescriptor {
                             dimension: Some(wgpu::TextureViewDimension::D2Array),
-                            array_layer_count: Some(VIEW_COUNT),
+                            array_layer_count: Some(view_count),
                             ..Default::default()
                         });
                         Texture::from_wgpu(texture, view)
@@ -487,26 +698,8 @@ impl XrState {
             }
         });
 
-        self.session.sync_actions(&[(&self.action_set).into()])?;
-        let locate_hand_pose = |action: &xr::Action<xr::Posef>,
-                                space: &xr::Space|
-         -> anyhow::Result<Option<(Vec3, Quat)>> {
-            if action.is_active(&self.session, xr::Path::NULL)? {
-                Ok(Some(openxr_pose_to_glam(
-                    &space
-                        .locate(&self.stage, xr_frame_state.predicted_display_time)?
-                        .pose,
-                )))
-            } else {
-                Ok(None)
-            }
-        };
-
-        let left_hand = locate_hand_pose(&self.left_action, &self.left_space)?;
-        let right_hand = locate_hand_pose(&self.right_action, &self.right_space)?;
-
         let (_, views) = self.session.locate_views(
-            VIEW_TYPE,
+            self.view_type,
             xr_frame_state.predicted_display_time,
             &self.stage,
         )?;
@@ -527,11 +720,62 @@ impl XrState {
 
         Ok(PostFrameData {
             views,
-            left_hand,
-            right_hand,
+            hands,
+            hand_joints,
         })
     }
 
+    /// Locates every joint of a hand at `time`, or returns `None` if the hand isn't tracked.
+    fn locate_hand_joints(
+        &self,
+        tracker: &xr::HandTracker,
+        time: xr::Time,
+    ) -> anyhow::Result<Option<HandJoints>> {
+        Ok(self
+            .stage
+            .locate_hand_joints(tracker, time)?
+            .map(|locations| locations.map(HandJoint::from)))
+    }
+
+    /// Reads the state of every action for `hand`, locating its poses at `time`. Actions that
+    /// aren't bound for the hand's current interaction profile are inactive.
+    fn hand_state(&self, hand: Hand, time: xr::Time) -> anyhow::Result<HandState> {
+        let hand_path = self.hand_paths[hand as usize];
+        let mut state = HandState::default();
+        for (desc, action) in ACTIONS.iter().zip(&self.actions) {
+            let value = match action {
+                XrAction::Boolean(action) => {
+                    ActionValue::Boolean(action.state(&self.session, hand_path)?.current_state)
+                }
+                XrAction::Float(action) => {
+                    ActionValue::Float(action.state(&self.session, hand_path)?.current_state)
+                }
+                XrAction::Vector2(action) => {
+                    let value = action.state(&self.session, hand_path)?.current_state;
+                    ActionValue::Vector2(vec2(value.x, value.y))
+                }
+                XrAction::Pose(action, spaces) => {
+                    let pose = if action.is_active(&self.session, hand_path)? {
+                        let location = spaces[hand as usize].locate(&self.stage, time)?;
+                        location
+                            .location_flags
+                            .contains(
+                                xr::SpaceLocationFlags::POSITION_VALID
+                                    | xr::SpaceLocationFlags::ORIENTATION_VALID,
+                            )
+                            .then(|| openxr_pose_to_glam(&location.pose))
+                    } else {
+                        None
+                    };
+                    ActionValue::Pose(pose)
+                }
+                XrAction::Haptic(_) => ActionValue::Haptic,
+            };
+            state.set(desc.id, value);
+        }
+        Ok(state)
+    }
+
     pub fn post_queue_submit(
         &mut self,
         xr_frame_state: xr::FrameState,
@@ -548,39 +792,81 @@ impl XrState {
                 },
             };
 
+            let projection_views: Vec<_> = views
+                .iter()
+                .enumerate()
+                .map(|(i, view)| {
+                    xr::CompositionLayerProjectionView::new()
+                        .pose(view.pose)
+                        .fov(view.fov)
+                        .sub_image(
+                            xr::SwapchainSubImage::new()
+                                .swapchain(&swapchain.handle)
+                                .image_array_index(i as u32)
+                                .image_rect(rect),
+                        )
+                })
+                .collect();
+
             self.frame_stream.end(
                 xr_frame_state.predicted_display_time,
                 self.environment_blend_mode,
                 &[&xr::CompositionLayerProjection::new()
                     .space(&self.stage)
-                    .views(&[
-                        xr::CompositionLayerProjectionView::new()
-                            .pose(views[0].pose)
-                            .fov(views[0].fov)
-                            .sub_image(
-                                xr::SwapchainSubImage::new()
-                                    .swapchain(&swapchain.handle)
-                                    .image_array_index(0)
-                                    .image_rect(rect),
-                            ),
-                        xr::CompositionLayerProjectionView::new()
-                            .pose(views[1].pose)
-                            .fov(views[1].fov)
-                            .sub_image(
-                                xr::SwapchainSubImage::new()
-                                    .swapchain(&swapchain.handle)
-                                    .image_array_index(1)
-                                    .image_rect(rect),
-                            ),
-                    ])],
+                    .views(&projection_views)],
             )?;
         }
 
         Ok(())
     }
 
-    pub fn views(&self) -> &[ViewConfigurationView] {
-        self.views.as_ref()
+    /// Vibrates `hand`'s controller, replacing any vibration that is still in progress.
+    pub fn apply_haptic_feedback(&self, hand: Hand, vibration: Vibration) -> anyhow::Result<()> {
+        let duration = if vibration.duration.is_zero() {
+            xr::Duration::MIN_HAPTIC
+        } else {
+            xr::Duration::from_nanos(vibration.duration.as_nanos() as i64)
+        };
+        self.haptic_action().apply_feedback(
+            &self.session,
+            self.hand_paths[hand as usize],
+            &xr::HapticVibration::new()
+                .duration(duration)
+                // 0 is XR_FREQUENCY_UNSPECIFIED.
+                .frequency(vibration.frequency.unwrap_or(0.0))
+                .amplitude(vibration.amplitude),
+        )?;
+        Ok(())
+    }
+
+    /// Stops any vibration of `hand`'s controller.
+    pub fn stop_haptic_feedback(&self, hand: Hand) -> anyhow::Result<()> {
+        self.haptic_action()
+            .stop_feedback(&self.session, self.hand_paths[hand as usize])?;
+        Ok(())
+    }
+
+    fn haptic_action(&self) -> &xr::Action<xr::Haptic> {
+        match &self.actions[ActionId::Haptic as usize] {
+            XrAction::Haptic(action) => action,
+            _ => unreachable!("the haptic action isn't a haptic action"),
+        }
+    }
+
+    /// The number of views in the view configuration, each of which is rendered to a layer of
+    /// the render target.
+    pub fn view_count(&self) -> u32 {
+        self.views.len() as u32
+    }
+
+    /// The resolution every view is rendered at: the largest recommended by any view.
+    pub fn resolution(&self) -> (u32, u32) {
+        self.views.iter().fold((0, 0), |(width, height), view| {
+            (
+                width.max(view.recommended_image_rect_width),
+                height.max(view.recommended_image_rect_height),
+            )
+        })
     }
 }
 
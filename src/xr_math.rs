@@ -0,0 +1,271 @@
+//! Conversions from OpenXR's poses and fields of view to the matrices used for rendering. These
+//! only work with glam types so that they can be used and tested without the `xr` feature.
+
+use glam::{vec3, Mat4, Quat, Vec3};
+
+use crate::types::DepthMode;
+
+/// The angles of the sides of a view's field of view from its forward direction, in radians.
+/// Mirrors `openxr::Fovf`: left and down are usually negative.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fov {
+    pub angle_left: f32,
+    pub angle_right: f32,
+    pub angle_up: f32,
+    pub angle_down: f32,
+}
+impl Fov {
+    /// A field of view centred on the forward direction, as used by [Mat4::perspective_rh].
+    pub fn symmetric(fov_y_rad: f32, aspect_ratio: f32) -> Self {
+        let half_fov_y = fov_y_rad / 2.0;
+        let half_fov_x = (half_fov_y.tan() * aspect_ratio).atan();
+        Self {
+            angle_left: -half_fov_x,
+            angle_right: half_fov_x,
+            angle_up: half_fov_y,
+            angle_down: -half_fov_y,
+        }
+    }
+}
+
+/// Converts a pose in OpenXR's stage space, given as its position and orientation, to the
+/// renderer's world space.
+///
+/// OpenXR looks down -Z while the renderer looks down +Z, so the world is the stage turned half
+/// a turn around +Y. This negates the X and Z components of both the position and the axis of
+/// the orientation; as it is a rotation rather than a reflection, handedness is preserved.
+pub fn pose_to_glam(position: Vec3, orientation: Quat) -> (Vec3, Quat) {
+    (
+        vec3(-position.x, position.y, -position.z),
+        Quat::from_xyzw(-orientation.x, orientation.y, -orientation.z, orientation.w),
+    )
+}
+
+/// The inverse of [pose_to_glam]. Turning half a turn twice is a full turn, so this is the same
+/// conversion.
+pub fn glam_to_pose(translation: Vec3, rotation: Quat) -> (Vec3, Quat) {
+    pose_to_glam(translation, rotation)
+}
+
+/// The view matrix for an eye at `translation` in world space that looks along `rotation * +Z`
+/// with `rotation * +Y` up.
+pub fn view_matrix(translation: Vec3, rotation: Quat) -> Mat4 {
+    Mat4::look_at_rh(
+        translation,
+        translation + rotation * Vec3::Z,
+        rotation * Vec3::Y,
+    )
+}
+
+/// A right-handed projection matrix for a possibly asymmetric field of view, mapping depth as
+/// described by `depth_mode`.
+pub fn fov_projection(fov: Fov, depth_mode: DepthMode, z_near: f32, z_far: f32) -> Mat4 {
+    let [tan_left, tan_right, tan_down, tan_up] = [
+        fov.angle_left,
+        fov.angle_right,
+        fov.angle_down,
+        fov.angle_up,
+    ]
+    .map(f32::tan);
+    let tan_width = tan_right - tan_left;
+    let tan_height = tan_up - tan_down;
+
+    let a11 = 2.0 / tan_width;
+    let a22 = 2.0 / tan_height;
+
+    let a31 = (tan_right + tan_left) / tan_width;
+    let a32 = (tan_up + tan_down) / tan_height;
+    let (a33, a43) = depth_mode.projection_depth_terms(z_near, z_far);
+
+    Mat4::from_cols_array(&[
+        a11, 0.0, 0.0, 0.0, //
+        0.0, a22, 0.0, 0.0, //
+        a31, a32, a33, -1.0, //
+        0.0, 0.0, a43, 0.0, //
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::EulerRot;
+
+    const EPSILON: f32 = 1e-5;
+    const Z_NEAR: f32 = 0.05;
+    const Z_FAR: f32 = 1000.0;
+
+    fn rotations() -> impl Iterator<Item = Quat> {
+        const ANGLES: [f32; 7] = [-3.0, -1.6, -0.4, 0.0, 0.3, 1.2, 2.5];
+        ANGLES.into_iter().flat_map(|yaw| {
+            ANGLES.into_iter().flat_map(move |pitch| {
+                ANGLES
+                    .into_iter()
+                    .map(move |roll| Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll))
+            })
+        })
+    }
+
+    fn positions() -> [Vec3; 4] {
+        [
+            Vec3::ZERO,
+            vec3(1.0, 2.0, 3.0),
+            vec3(-0.5, 1.7, 0.25),
+            vec3(10.0, -3.0, -7.5),
+        ]
+    }
+
+    fn assert_same_rotation(a: Quat, b: Quat) {
+        // `q` and `-q` are the same rotation.
+        assert!(a.dot(b).abs() > 1.0 - EPSILON, "{a:?} != {b:?}");
+    }
+
+    fn project(projection: Mat4, point: Vec3) -> Vec3 {
+        projection.project_point3(point)
+    }
+
+    #[test]
+    fn pose_round_trips() {
+        for rotation in rotations() {
+            for position in positions() {
+                let (translation, glam_rotation) = pose_to_glam(position, rotation);
+                let (round_position, round_rotation) = glam_to_pose(translation, glam_rotation);
+                assert!(round_position.abs_diff_eq(position, EPSILON));
+                assert_same_rotation(round_rotation, rotation);
+
+                let (xr_position, xr_rotation) = glam_to_pose(position, rotation);
+                let (round_translation, round_glam_rotation) =
+                    pose_to_glam(xr_position, xr_rotation);
+                assert!(round_translation.abs_diff_eq(position, EPSILON));
+                assert_same_rotation(round_glam_rotation, rotation);
+            }
+        }
+    }
+
+    #[test]
+    fn known_poses() {
+        // The identity pose looks down OpenXR's -Z, which becomes the renderer's +Z.
+        let (translation, rotation) = pose_to_glam(vec3(1.0, 2.0, 3.0), Quat::IDENTITY);
+        assert!(translation.abs_diff_eq(vec3(-1.0, 2.0, -3.0), EPSILON));
+        assert_same_rotation(rotation, Quat::IDENTITY);
+
+        // Turning left by a quarter turn looks down OpenXR's -X, which becomes the renderer's +X.
+        let (_, rotation) = pose_to_glam(Vec3::ZERO, Quat::from_rotation_y(90f32.to_radians()));
+        assert!((rotation * Vec3::Z).abs_diff_eq(Vec3::X, EPSILON));
+        assert!((rotation * Vec3::Y).abs_diff_eq(Vec3::Y, EPSILON));
+
+        // Looking up stays looking up.
+        let (_, rotation) = pose_to_glam(Vec3::ZERO, Quat::from_rotation_x(90f32.to_radians()));
+        assert!((rotation * Vec3::Z).abs_diff_eq(Vec3::Y, EPSILON));
+    }
+
+    #[test]
+    fn conversion_is_a_half_turn_around_y() {
+        let half_turn = Quat::from_rotation_y(180f32.to_radians());
+        let local_points = [Vec3::X, Vec3::Y, Vec3::Z, vec3(0.3, -0.7, 1.1)];
+        for rotation in rotations() {
+            for position in positions() {
+                let (translation, glam_rotation) = pose_to_glam(position, rotation);
+                for point in local_points {
+                    // Transforming a point in OpenXR and then converting it must agree with
+                    // converting the pose and transforming the converted point.
+                    let expected = half_turn * (rotation * point + position);
+                    let actual = glam_rotation * (half_turn * point) + translation;
+                    assert!(actual.abs_diff_eq(expected, 1e-4), "{actual} != {expected}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_fov_matches_perspective_rh() {
+        for fov_y_deg in [30.0f32, 60.0, 90.0, 110.0] {
+            for aspect_ratio in [0.5, 1.0, 16.0 / 9.0] {
+                let fov_y_rad = fov_y_deg.to_radians();
+                let projection = fov_projection(
+                    Fov::symmetric(fov_y_rad, aspect_ratio),
+                    DepthMode::Standard,
+                    Z_NEAR,
+                    Z_FAR,
+                );
+                let expected = Mat4::perspective_rh(fov_y_rad, aspect_ratio, Z_NEAR, Z_FAR);
+                assert!(
+                    projection.abs_diff_eq(expected, EPSILON),
+                    "{projection} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fov_edges_map_to_ndc_edges() {
+        let fov = Fov {
+            angle_left: -0.9,
+            angle_right: 0.7,
+            angle_up: 0.8,
+            angle_down: -0.95,
+        };
+        let projection = fov_projection(fov, DepthMode::ReverseZ, Z_NEAR, Z_FAR);
+        for distance in [0.1, 1.0, 50.0] {
+            let edge = |x: f32, y: f32| vec3(x.tan() * distance, y.tan() * distance, -distance);
+            assert!((project(projection, edge(fov.angle_left, 0.0)).x + 1.0).abs() < EPSILON);
+            assert!((project(projection, edge(fov.angle_right, 0.0)).x - 1.0).abs() < EPSILON);
+            assert!((project(projection, edge(0.0, fov.angle_down)).y + 1.0).abs() < EPSILON);
+            assert!((project(projection, edge(0.0, fov.angle_up)).y - 1.0).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn depth_modes_map_near_and_far_planes() {
+        let fov = Fov::symmetric(90f32.to_radians(), 1.0);
+        let depth = |depth_mode, distance: f32| {
+            project(
+                fov_projection(fov, depth_mode, Z_NEAR, Z_FAR),
+                vec3(0.0, 0.0, -distance),
+            )
+            .z
+        };
+
+        assert!(depth(DepthMode::Standard, Z_NEAR).abs() < EPSILON);
+        assert!((depth(DepthMode::Standard, Z_FAR) - 1.0).abs() < EPSILON);
+        assert!((depth(DepthMode::ReverseZ, Z_NEAR) - 1.0).abs() < EPSILON);
+        assert!(depth(DepthMode::ReverseZ, Z_FAR).abs() < EPSILON);
+        assert!((depth(DepthMode::ReverseZInfinite, Z_NEAR) - 1.0).abs() < EPSILON);
+        assert!(depth(DepthMode::ReverseZInfinite, 1e9) < EPSILON);
+
+        // Closer points must pass the depth test against farther ones.
+        for depth_mode in [
+            DepthMode::Standard,
+            DepthMode::ReverseZ,
+            DepthMode::ReverseZInfinite,
+        ] {
+            let (near, far) = (depth(depth_mode, 1.0), depth(depth_mode, 2.0));
+            match depth_mode.compare_function() {
+                wgpu::CompareFunction::Less => assert!(near < far),
+                wgpu::CompareFunction::Greater => assert!(near > far),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn openxr_directions_keep_their_place_on_screen() {
+        let (translation, rotation) = pose_to_glam(Vec3::ZERO, Quat::IDENTITY);
+        let view_projection = fov_projection(
+            Fov::symmetric(90f32.to_radians(), 1.0),
+            DepthMode::ReverseZ,
+            Z_NEAR,
+            Z_FAR,
+        ) * view_matrix(translation, rotation);
+        let ndc = |xr_point: Vec3| {
+            let (point, _) = pose_to_glam(xr_point, Quat::IDENTITY);
+            project(view_projection, point)
+        };
+
+        // OpenXR is right-handed with +X right, +Y up and -Z forward.
+        let ahead = ndc(vec3(0.0, 0.0, -1.0));
+        assert!(ahead.truncate().abs_diff_eq(glam::Vec2::ZERO, EPSILON));
+        assert!(ahead.z > 0.0 && ahead.z < 1.0);
+        assert!(ndc(vec3(0.5, 0.0, -1.0)).x > 0.0);
+        assert!(ndc(vec3(0.0, 0.5, -1.0)).y > 0.0);
+    }
+}
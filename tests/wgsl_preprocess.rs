@@ -0,0 +1,40 @@
+//! Runs the `wgsl_preprocess` tool over a small shader directory.
+
+use std::process::Command;
+
+#[test]
+fn writes_entry_points_and_line_maps() {
+    let root = std::env::temp_dir().join(format!("wgsl-preprocess-{}", std::process::id()));
+    let (input, output) = (root.join("shaders"), root.join("out"));
+    std::fs::create_dir_all(input.join("lighting")).unwrap();
+    std::fs::write(
+        input.join("main.wgsl"),
+        "#include lighting/pbr.wgsl\nconst view_count = VIEW_COUNT;",
+    )
+    .unwrap();
+    std::fs::write(input.join("lighting/pbr.wgsl"), "#pragma once\n// pbr").unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_wgsl_preprocess"))
+        .arg(&input)
+        .arg(&output)
+        .args(["-D", "VIEW_COUNT=2", "--line-map"])
+        .output()
+        .unwrap();
+    let main = std::fs::read_to_string(output.join("main.wgsl"));
+    let main_map = std::fs::read_to_string(output.join("main.wgsl.map"));
+    let pbr_written = output.join("lighting/pbr.wgsl").exists();
+    std::fs::remove_dir_all(&root).unwrap();
+
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert_eq!(main.unwrap(), "// pbr\nconst view_count = 2;");
+    let main_map = main_map.unwrap();
+    let lines: Vec<_> = main_map.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("1 ") && lines[0].ends_with("pbr.wgsl:2"));
+    assert_eq!(lines[1], "2 main.wgsl:2");
+    assert!(!pbr_written, "included files are not entry points");
+}